@@ -0,0 +1,28 @@
+//! Lists the directories in `migrations`, or `migrations_sqlite` with the `sqlite` feature, for
+//! `sk_rust_web::migrations`. `embed_migrations!` builds the same directories into the binary
+//! but keeps its list of them to itself, and the status endpoint needs it to tell which
+//! migrations are still pending.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+    println!("cargo:rerun-if-changed=migrations_sqlite");
+
+    let directory = if env::var_os("CARGO_FEATURE_SQLITE").is_some() { "migrations_sqlite" } else { "migrations" };
+    let mut names = fs::read_dir(directory)
+        .expect("Couldn't read the migrations directory")
+        .map(|entry| entry.expect("Couldn't read the migrations directory"))
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect::<Vec<_>>();
+    names.sort();
+
+    let listing = names.iter().map(|name| format!("    {:?},\n", name)).collect::<String>();
+    let out = Path::new(&env::var("OUT_DIR").expect("OUT_DIR is set by cargo")).join("migrations.rs");
+    fs::write(out, format!("const MIGRATION_NAMES: &[&str] = &[\n{}];\n", listing))
+        .expect("Couldn't write the list of migrations");
+}
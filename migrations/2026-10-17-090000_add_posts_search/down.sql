@@ -0,0 +1,3 @@
+DROP INDEX posts_searchable_idx;
+
+ALTER TABLE posts DROP COLUMN searchable;
@@ -0,0 +1,6 @@
+ALTER TABLE posts ADD COLUMN searchable TSVECTOR NOT NULL GENERATED ALWAYS AS (
+  setweight(to_tsvector('english', title), 'A') ||
+  setweight(to_tsvector('english', body), 'B')
+) STORED;
+
+CREATE INDEX posts_searchable_idx ON posts USING GIN (searchable);
@@ -0,0 +1,7 @@
+ALTER TABLE posts ADD COLUMN published BOOLEAN NOT NULL DEFAULT 'f';
+
+UPDATE posts SET published = 't' WHERE status = 'published';
+
+ALTER TABLE posts DROP COLUMN status;
+
+DROP TYPE post_status;
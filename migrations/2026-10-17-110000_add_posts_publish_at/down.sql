@@ -0,0 +1,3 @@
+DROP INDEX posts_publish_at_idx;
+
+ALTER TABLE posts DROP COLUMN publish_at;
@@ -0,0 +1,4 @@
+ALTER TABLE posts ADD COLUMN publish_at TIMESTAMPTZ;
+
+-- Only posts waiting in review get picked up by the publisher, so that's all we index.
+CREATE INDEX posts_publish_at_idx ON posts (publish_at) WHERE status = 'review';
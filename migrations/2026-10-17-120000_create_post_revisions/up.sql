@@ -0,0 +1,10 @@
+CREATE TABLE post_revisions (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  editor VARCHAR,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX post_revisions_post_id_idx ON post_revisions (post_id, id);
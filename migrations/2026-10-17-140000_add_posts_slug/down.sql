@@ -0,0 +1,3 @@
+DROP TABLE post_slugs;
+
+ALTER TABLE posts DROP COLUMN slug;
@@ -0,0 +1,27 @@
+ALTER TABLE posts ADD COLUMN slug VARCHAR;
+
+-- Backfill existing posts with an ASCII only approximation of the slugs the app generates,
+-- numbering duplicates in id order. New and renamed posts get fully transliterated slugs.
+WITH base AS (
+  SELECT id,
+         coalesce(nullif(trim(both '-' from lower(regexp_replace(title, '[^a-zA-Z0-9]+', '-', 'g'))), ''), 'post') AS slug
+  FROM posts
+), numbered AS (
+  SELECT id, slug, row_number() OVER (PARTITION BY slug ORDER BY id) AS n
+  FROM base
+)
+UPDATE posts
+SET slug = CASE WHEN numbered.n = 1 THEN numbered.slug ELSE numbered.slug || '-' || numbered.n END
+FROM numbered
+WHERE posts.id = numbered.id;
+
+ALTER TABLE posts ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE posts ADD CONSTRAINT posts_slug_key UNIQUE (slug);
+
+CREATE TABLE post_slugs (
+  slug VARCHAR PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX post_slugs_post_id_idx ON post_slugs (post_id);
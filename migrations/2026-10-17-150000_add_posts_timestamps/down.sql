@@ -0,0 +1,5 @@
+DROP TRIGGER set_updated_at ON posts;
+
+ALTER TABLE posts
+  DROP COLUMN created_at,
+  DROP COLUMN updated_at;
@@ -0,0 +1,9 @@
+-- Posts that already exist get the time of the migration, there is nothing older to go by.
+ALTER TABLE posts
+  ADD COLUMN created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+  ADD COLUMN updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+
+CREATE INDEX posts_created_at_idx ON posts (created_at, id);
+CREATE INDEX posts_updated_at_idx ON posts (updated_at, id);
+
+SELECT diesel_manage_updated_at('posts');
@@ -0,0 +1,14 @@
+CREATE TYPE comment_status AS ENUM ('pending', 'approved', 'spam');
+
+CREATE TABLE comments (
+  id SERIAL PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  parent_id INTEGER REFERENCES comments (id) ON DELETE CASCADE,
+  author VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  status comment_status NOT NULL DEFAULT 'pending',
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX comments_post_id_idx ON comments (post_id, status);
+CREATE INDEX comments_parent_id_idx ON comments (parent_id);
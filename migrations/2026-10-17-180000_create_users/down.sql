@@ -0,0 +1,3 @@
+ALTER TABLE posts DROP COLUMN author_id;
+
+DROP TABLE users;
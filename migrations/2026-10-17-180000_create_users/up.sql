@@ -0,0 +1,18 @@
+CREATE TABLE users (
+  id SERIAL PRIMARY KEY,
+  username VARCHAR NOT NULL UNIQUE,
+  display_name VARCHAR NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- `/set/message` signs everybody in as "sk", who wrote every post there is so far.
+INSERT INTO users (username, display_name) VALUES ('sk', 'sk');
+
+ALTER TABLE posts ADD COLUMN author_id INTEGER REFERENCES users (id);
+
+-- Crediting the author isn't an edit, so it leaves `updated_at` alone.
+ALTER TABLE posts DISABLE TRIGGER set_updated_at;
+UPDATE posts SET author_id = (SELECT id FROM users WHERE username = 'sk');
+ALTER TABLE posts ENABLE TRIGGER set_updated_at;
+
+CREATE INDEX posts_author_id_idx ON posts (author_id);
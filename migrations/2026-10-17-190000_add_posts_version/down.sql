@@ -0,0 +1,4 @@
+DROP TRIGGER bump_version ON posts;
+DROP FUNCTION bump_version();
+
+ALTER TABLE posts DROP COLUMN version;
@@ -0,0 +1,13 @@
+ALTER TABLE posts ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Every write to a post moves it on a version, whichever code path it comes from, so that an
+-- edit based on an older version can be told apart and refused.
+CREATE FUNCTION bump_version() RETURNS trigger AS $$
+BEGIN
+    NEW.version := OLD.version + 1;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER bump_version BEFORE UPDATE ON posts
+    FOR EACH ROW EXECUTE PROCEDURE bump_version();
@@ -0,0 +1,7 @@
+DROP TABLE comments;
+DROP TABLE post_tags;
+DROP TABLE tags;
+DROP TABLE post_slugs;
+DROP TABLE post_revisions;
+DROP TABLE posts;
+DROP TABLE users;
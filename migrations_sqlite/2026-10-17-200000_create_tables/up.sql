@@ -0,0 +1,90 @@
+-- The tables as they stand after the Postgres migrations, for SQLite. Statuses are text checked
+-- against the same values as the Postgres enums, timestamps are UTC text the way Diesel writes
+-- them, and there is no search column; search matches the title and body directly instead.
+CREATE TABLE users (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  username VARCHAR NOT NULL UNIQUE,
+  display_name VARCHAR NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+-- `/set/message` signs everybody in as "sk".
+INSERT INTO users (username, display_name) VALUES ('sk', 'sk');
+
+CREATE TABLE posts (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'review', 'published', 'archived')),
+  publish_at TIMESTAMP,
+  deleted_at TIMESTAMP,
+  slug VARCHAR NOT NULL UNIQUE,
+  created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+  updated_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+  author_id INTEGER REFERENCES users (id),
+  version INTEGER NOT NULL DEFAULT 1
+);
+
+CREATE INDEX posts_publish_at_idx ON posts (publish_at) WHERE status = 'review';
+CREATE INDEX posts_deleted_at_idx ON posts (deleted_at) WHERE deleted_at IS NOT NULL;
+CREATE INDEX posts_created_at_idx ON posts (created_at, id);
+CREATE INDEX posts_updated_at_idx ON posts (updated_at, id);
+CREATE INDEX posts_author_id_idx ON posts (author_id);
+
+-- What the `bump_version` and `set_updated_at` triggers do on Postgres: every write to a post
+-- moves it on a version, and bumps `updated_at` unless the write set it itself. Triggers don't
+-- fire recursively unless asked to, so the trigger's own update doesn't set it off again.
+CREATE TRIGGER bump_version AFTER UPDATE ON posts FOR EACH ROW
+BEGIN
+  UPDATE posts
+  SET version = OLD.version + 1,
+      updated_at = CASE WHEN NEW.updated_at IS OLD.updated_at
+                        THEN strftime('%Y-%m-%d %H:%M:%f', 'now')
+                        ELSE NEW.updated_at END
+  WHERE id = NEW.id;
+END;
+
+CREATE TABLE post_revisions (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  title VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  editor VARCHAR,
+  created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+CREATE INDEX post_revisions_post_id_idx ON post_revisions (post_id, id);
+
+CREATE TABLE post_slugs (
+  slug VARCHAR PRIMARY KEY,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+CREATE INDEX post_slugs_post_id_idx ON post_slugs (post_id);
+
+CREATE TABLE tags (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  name VARCHAR NOT NULL UNIQUE
+);
+
+CREATE TABLE post_tags (
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+  PRIMARY KEY (post_id, tag_id)
+);
+
+CREATE INDEX post_tags_tag_id_idx ON post_tags (tag_id);
+
+CREATE TABLE comments (
+  id INTEGER PRIMARY KEY AUTOINCREMENT,
+  post_id INTEGER NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+  parent_id INTEGER REFERENCES comments (id) ON DELETE CASCADE,
+  author VARCHAR NOT NULL,
+  body TEXT NOT NULL,
+  status VARCHAR NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'spam')),
+  created_at TIMESTAMP NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+);
+
+CREATE INDEX comments_post_id_idx ON comments (post_id, status);
+CREATE INDEX comments_parent_id_idx ON comments (parent_id);
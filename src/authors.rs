@@ -0,0 +1,30 @@
+//! Post authors: finding the user behind a sign-in and loading the authors of a page of posts.
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+
+use crate::connection_pool::DbConnection;
+use crate::models::{Post, User};
+use crate::schema::users;
+
+/// The id of the user signed in as `username`, if there is such a user.
+pub fn find_id(connection: &DbConnection, username: &str) -> QueryResult<Option<i32>> {
+    users::table
+        .filter(users::username.eq(username))
+        .select(users::id)
+        .first::<i32>(connection)
+        .optional()
+}
+
+/// The author of each of `posts`, in the same order as `posts`.
+pub fn for_posts(connection: &DbConnection, posts: &[Post]) -> QueryResult<Vec<Option<User>>> {
+    let author_ids = posts.iter().filter_map(|post| post.author_id).collect::<Vec<_>>();
+    let authors = users::table
+        .filter(users::id.eq_any(author_ids))
+        .load::<User>(connection)?
+        .into_iter()
+        .map(|user| (user.id, user))
+        .collect::<HashMap<_, _>>();
+    Ok(posts.iter().map(|post| post.author_id.and_then(|id| authors.get(&id).cloned())).collect())
+}
@@ -0,0 +1,26 @@
+//! Periodic database jobs that run on their own threads next to the web server.
+
+use std::thread;
+use std::time::Duration;
+
+use crate::connection_pool::{DbConnection, Pool};
+
+/// Starts a thread named `name` that runs `job` every `interval`. Every run takes a connection
+/// from `pool` and hands it back afterwards, so a database restart doesn't take the job down
+/// with it.
+pub fn spawn_periodic<F>(name: &str, pool: Pool, interval: Duration, job: F)
+    where F: Fn(&DbConnection) + Send + 'static
+{
+    let thread_name = name.to_string();
+    let name = name.to_string();
+    thread::Builder::new()
+        .name(thread_name)
+        .spawn(move || loop {
+            match pool.get() {
+                Ok(connection) => job(&connection),
+                Err(error) => println!("{} couldn't connect to the database: {}", name, error),
+            }
+            thread::sleep(interval);
+        })
+        .unwrap_or_else(|error| panic!("Failed to spawn {}: {}", name, error));
+}
@@ -0,0 +1,116 @@
+//! HTTP caching headers for responses, and answering conditional requests.
+
+use std::io::Cursor;
+
+use chrono::{DateTime, Utc};
+use rocket::http::Status;
+use rocket::request::Request;
+use rocket::response::{self, Responder, Response};
+use sha2::{Digest, Sha256};
+
+/// A response that caches and proxies may keep for `max_age` seconds.
+pub struct Cached<R> {
+    pub max_age: u32,
+    pub response: R,
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Cached<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = self.response.respond_to(request)?;
+        response.set_raw_header("Cache-Control", format!("public, max-age={}", self.max_age));
+        Ok(response)
+    }
+}
+
+/// A response carrying a strong `ETag`, the SHA-256 of its body, and a `Last-Modified` date.
+/// A request whose `If-None-Match` or `If-Modified-Since` shows the client already has it is
+/// answered with an empty 304 instead, which keeps the `Vary` of the response it stands for.
+pub struct Conditional<R> {
+    pub last_modified: Option<DateTime<Utc>>,
+    /// Row version put in front of the ETag, so that writes can name it in `If-Match`.
+    pub version: Option<i32>,
+    pub response: R,
+}
+
+/// The ETag of a response with `body`, such as `"3-9f86d0…"` for version 3 of a post.
+pub fn etag(version: Option<i32>, body: &[u8]) -> String {
+    match version {
+        Some(version) => format!("\"{}-{:x}\"", version, Sha256::digest(body)),
+        None => format!("\"{:x}\"", Sha256::digest(body)),
+    }
+}
+
+/// Formats `time` as an HTTP date, such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+pub fn http_date(time: DateTime<Utc>) -> String {
+    time.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    // `If-None-Match` uses the weak comparison, so a `W/` prefix doesn't stop a match.
+    if_none_match.split(',')
+        .map(|candidate| candidate.trim())
+        .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+/// Whether the client's copy, as described by its conditional headers, is still current.
+/// `If-Modified-Since` only counts when there is no `If-None-Match`, as RFC 7232 has it.
+fn not_modified(request: &Request, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = request.headers().get_one("If-None-Match") {
+        return etag_matches(if_none_match, etag);
+    }
+
+    let since = request.headers().get_one("If-Modified-Since")
+        .and_then(|since| DateTime::parse_from_rfc2822(since).ok());
+    match (since, last_modified) {
+        // HTTP dates have whole seconds, so the fraction of `last_modified` is ignored.
+        (Some(since), Some(last_modified)) => last_modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+impl<'r, R: Responder<'r>> Responder<'r> for Conditional<R> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = self.response.respond_to(request)?;
+        if response.status() != Status::Ok {
+            return Ok(response);
+        }
+
+        let body = response.body_bytes().unwrap_or_default();
+        let etag = etag(self.version, &body);
+        let mut headers = Response::build();
+        headers.raw_header("ETag", etag.clone());
+        if let Some(last_modified) = self.last_modified {
+            headers.raw_header("Last-Modified", http_date(last_modified));
+        }
+
+        if not_modified(request, &etag, self.last_modified) {
+            // Caches holding one representation per `Accept` need to know which one is current.
+            if let Some(vary) = response.headers().get_one("Vary") {
+                headers.raw_header("Vary", vary.to_string());
+            }
+            return headers.status(Status::NotModified).ok();
+        }
+        response.set_sized_body(Cursor::new(body));
+        response.merge(headers.finalize());
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{etag, etag_matches};
+
+    #[test]
+    fn if_none_match_compares_weakly() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"xyz\", W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"abcd\"", "\"abc\""));
+    }
+
+    #[test]
+    fn version_leads_the_etag() {
+        assert!(etag(Some(3), b"body").starts_with("\"3-"));
+        assert!(!etag(None, b"body").contains('-'));
+    }
+}
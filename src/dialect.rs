@@ -0,0 +1,78 @@
+//! The few things Postgres and SQLite spell differently, so that queries are written once
+//! whichever of them the app is built for.
+
+use diesel::dsl::{sql, SqlLiteral};
+use diesel::expression::bound::Bound;
+use diesel::prelude::*;
+use diesel::sql_types::Integer;
+
+use crate::connection_pool::DbConnection;
+use crate::sql_types::Timestamptz;
+
+#[cfg(not(feature = "sqlite"))]
+const NOW: &str = "CURRENT_TIMESTAMP";
+// The same text Diesel writes a timestamp as, so that timestamps compare in order.
+#[cfg(feature = "sqlite")]
+const NOW: &str = "strftime('%Y-%m-%d %H:%M:%f', 'now')";
+
+#[cfg(not(feature = "sqlite"))]
+const LAST_INSERT_ID: &str = "lastval()::integer";
+#[cfg(feature = "sqlite")]
+const LAST_INSERT_ID: &str = "last_insert_rowid()";
+
+/// The current time, as the database sees it.
+pub fn now() -> SqlLiteral<Timestamptz> {
+    sql::<Timestamptz>(NOW)
+}
+
+/// The id of the row `connection` last inserted, where Postgres would answer with `RETURNING`.
+pub fn last_insert_id(connection: &DbConnection) -> QueryResult<i32> {
+    diesel::select(sql::<Integer>(LAST_INSERT_ID)).get_result(connection)
+}
+
+/// `value` as a parameter of the same type as `column`. On SQLite a `DateTime` can't be
+/// compared with a timestamp column directly, so timestamps are bound through this.
+pub fn bind<C: Expression, T>(_column: C, value: T) -> Bound<C::SqlType, T> {
+    Bound::new(value)
+}
+
+/// Escapes the `LIKE` wildcards in user input so it is matched literally, with `\` as the
+/// escape character.
+pub fn like_pattern(fragment: &str) -> String {
+    let escaped = fragment.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+    format!("%{}%", escaped)
+}
+
+/// Runs `f` in a transaction for a read that is followed by a write. Postgres locks the rows
+/// read `FOR UPDATE`, while SQLite can only take the whole database, so it does so up front.
+#[cfg(not(feature = "sqlite"))]
+pub fn write_transaction<T, E, F>(connection: &DbConnection, f: F) -> Result<T, E>
+    where F: FnOnce() -> Result<T, E>, E: From<diesel::result::Error>
+{
+    connection.transaction(f)
+}
+
+/// On SQLite, `BEGIN IMMEDIATE`.
+#[cfg(feature = "sqlite")]
+pub fn write_transaction<T, E, F>(connection: &DbConnection, f: F) -> Result<T, E>
+    where F: FnOnce() -> Result<T, E>, E: From<diesel::result::Error>
+{
+    connection.immediate_transaction(f)
+}
+
+/// Inserts `$values` into `$table`, skipping the rows that would break a unique constraint.
+/// On Postgres, `ON CONFLICT DO NOTHING`.
+#[cfg(not(feature = "sqlite"))]
+macro_rules! insert_or_ignore {
+    ($table:expr, $values:expr) => {
+        diesel::insert_into($table).values($values).on_conflict_do_nothing()
+    };
+}
+
+/// On SQLite, `INSERT OR IGNORE`.
+#[cfg(feature = "sqlite")]
+macro_rules! insert_or_ignore {
+    ($table:expr, $values:expr) => {
+        diesel::insert_or_ignore_into($table).values($values)
+    };
+}
@@ -0,0 +1,83 @@
+//! Line based diffs between two versions of a text, used to compare post revisions.
+
+use serde::Serialize;
+
+/// One line of a diff, serialized as `{"op": "insert", "line": "..."}`.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(tag = "op", content = "line", rename_all = "lowercase")]
+pub enum DiffLine {
+    Equal(String),
+    Insert(String),
+    Delete(String),
+}
+
+/// Diffs `old` against `new` line by line, using the longest common subsequence of lines so
+/// that unchanged lines are reported as such wherever possible.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Edits tend to be local, so peel off the common prefix and suffix before building the
+    // quadratic LCS table over whatever is left in between.
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_middle, new_middle) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+
+    // lcs[i][j] is the length of the longest common subsequence of old_middle[i..] and new_middle[j..].
+    let mut lcs = vec![vec![0usize; new_middle.len() + 1]; old_middle.len() + 1];
+    for i in (0..old_middle.len()).rev() {
+        for j in (0..new_middle.len()).rev() {
+            lcs[i][j] = if old_middle[i] == new_middle[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<DiffLine> = old[..prefix].iter().map(|line| DiffLine::Equal(line.to_string())).collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_middle.len() && j < new_middle.len() {
+        if old_middle[i] == new_middle[j] {
+            diff.push(DiffLine::Equal(old_middle[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(DiffLine::Delete(old_middle[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Insert(new_middle[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(old_middle[i..].iter().map(|line| DiffLine::Delete(line.to_string())));
+    diff.extend(new_middle[j..].iter().map(|line| DiffLine::Insert(line.to_string())));
+    diff.extend(old[old.len() - suffix..].iter().map(|line| DiffLine::Equal(line.to_string())));
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use super::DiffLine::*;
+
+    #[test]
+    fn diff_reports_changed_lines() {
+        let diff = diff_lines("a\nb\nc\nd", "a\nc\nx\nd");
+        assert_eq!(diff, vec![
+            Equal("a".into()),
+            Delete("b".into()),
+            Equal("c".into()),
+            Insert("x".into()),
+            Equal("d".into()),
+        ]);
+    }
+
+    #[test]
+    fn diff_of_identical_texts_is_all_equal() {
+        let diff = diff_lines("one\ntwo", "one\ntwo");
+        assert_eq!(diff, vec![Equal("one".into()), Equal("two".into())]);
+    }
+}
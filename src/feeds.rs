@@ -0,0 +1,110 @@
+//! RSS 2.0 and Atom feeds of published posts.
+
+use chrono::{DateTime, Utc};
+
+use crate::site::Site;
+
+/// How many of the newest posts a feed carries.
+pub const FEED_SIZE: i64 = 20;
+
+/// The feed itself: what it is about and where it can be fetched.
+pub struct Channel {
+    pub title: String,
+    /// The page the feed is about, such as the tag's listing.
+    pub link: String,
+    /// The feed's own URL.
+    pub self_link: String,
+}
+
+/// A post as it appears in a feed.
+pub struct Entry {
+    pub title: String,
+    pub url: String,
+    pub author: Option<String>,
+    pub html: String,
+    pub published: DateTime<Utc>,
+    pub updated: DateTime<Utc>,
+}
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn rss(channel: &Channel, entries: &[Entry]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n<channel>\n");
+    xml.push_str(&format!("<title>{}</title>\n", escape(&channel.title)));
+    xml.push_str(&format!("<link>{}</link>\n", escape(&channel.link)));
+    xml.push_str(&format!("<description>{}</description>\n", escape(&channel.title)));
+    xml.push_str(&format!("<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+                          escape(&channel.self_link)));
+    if let Some(entry) = entries.first() {
+        xml.push_str(&format!("<lastBuildDate>{}</lastBuildDate>\n", entry.published.to_rfc2822()));
+    }
+
+    for entry in entries {
+        xml.push_str("<item>\n");
+        xml.push_str(&format!("<title>{}</title>\n", escape(&entry.title)));
+        xml.push_str(&format!("<link>{}</link>\n", escape(&entry.url)));
+        xml.push_str(&format!("<guid isPermaLink=\"true\">{}</guid>\n", escape(&entry.url)));
+        xml.push_str(&format!("<pubDate>{}</pubDate>\n", entry.published.to_rfc2822()));
+        xml.push_str(&format!("<description>{}</description>\n", escape(&entry.html)));
+        xml.push_str("</item>\n");
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+pub fn atom(channel: &Channel, site: &Site, entries: &[Entry]) -> String {
+    let updated = entries.iter().map(|entry| entry.updated).max().unwrap_or_else(Utc::now);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("<id>{}</id>\n", escape(&channel.self_link)));
+    xml.push_str(&format!("<title>{}</title>\n", escape(&channel.title)));
+    xml.push_str(&format!("<updated>{}</updated>\n", updated.to_rfc3339()));
+    xml.push_str(&format!("<link href=\"{}\"/>\n", escape(&channel.link)));
+    xml.push_str(&format!("<link href=\"{}\" rel=\"self\" type=\"application/atom+xml\"/>\n",
+                          escape(&channel.self_link)));
+    // Atom wants an author on every entry, which the feed's own stands in for.
+    xml.push_str(&format!("<author><name>{}</name></author>\n", escape(&site.title)));
+
+    for entry in entries {
+        xml.push_str("<entry>\n");
+        xml.push_str(&format!("<id>{}</id>\n", escape(&entry.url)));
+        xml.push_str(&format!("<title>{}</title>\n", escape(&entry.title)));
+        xml.push_str(&format!("<link href=\"{}\"/>\n", escape(&entry.url)));
+        xml.push_str(&format!("<published>{}</published>\n", entry.published.to_rfc3339()));
+        xml.push_str(&format!("<updated>{}</updated>\n", entry.updated.to_rfc3339()));
+        if let Some(ref author) = entry.author {
+            xml.push_str(&format!("<author><name>{}</name></author>\n", escape(author)));
+        }
+        xml.push_str(&format!("<content type=\"html\">{}</content>\n", escape(&entry.html)));
+        xml.push_str("</entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+#[cfg(test)]
+mod test {
+    use super::escape;
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(escape("<p>Tom & \"Jerry\"</p>"), "&lt;p&gt;Tom &amp; &quot;Jerry&quot;&lt;/p&gt;");
+    }
+}
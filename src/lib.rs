@@ -1,20 +1,34 @@
 pub mod schema;
+pub mod sql_types;
+#[macro_use]
+pub mod dialect;
 pub mod models;
+pub mod authors;
+pub mod background;
+pub mod caching;
 pub mod connection_pool;
 pub mod counter_fairing;
+pub mod diff;
+pub mod feeds;
+pub mod markdown;
+pub mod migrations;
+pub mod negotiation;
+pub mod pagination;
+pub mod publisher;
+pub mod repository;
+pub mod site;
+pub mod sitemap;
+pub mod slugs;
+pub mod tagging;
+pub mod threads;
+pub mod transfer;
+pub mod trash;
+
+#[cfg(not(any(feature = "postgres", feature = "sqlite")))]
+compile_error!("Build with the `postgres` feature, the default, or with `sqlite`.");
 
 #[macro_use]
 extern crate diesel;
+#[macro_use]
+extern crate diesel_migrations;
 extern crate dotenv;
-use diesel::prelude::*;
-use diesel::pg::PgConnection;
-use dotenv::dotenv;
-use std::env;
-pub fn establish_connection() -> PgConnection {
-    dotenv().ok();
-
-    let database_url = env::var("DATABASE_URL")
-        .expect("DATABASE_URL must be set");
-    PgConnection::establish(&database_url)
-        .expect(&format!("Error connecting to {}", database_url))
-}
\ No newline at end of file
@@ -0,0 +1,180 @@
+//! Rendering post bodies from Markdown to HTML that is safe to put on a page.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use ammonia::Builder;
+use pulldown_cmark::{html, Options, Parser};
+
+use crate::models::Post;
+
+/// Elements the rendered HTML may keep, which is everything CommonMark, tables and
+/// strikethrough produce. Anything else, raw HTML included, is stripped by the sanitizer.
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "blockquote", "br", "code", "del", "em", "h1", "h2", "h3", "h4", "h5", "h6", "hr", "img",
+    "li", "ol", "p", "pre", "strong", "table", "tbody", "td", "th", "thead", "tr", "ul",
+];
+
+const ALLOWED_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href", "title"]),
+    ("img", &["src", "alt", "title"]),
+    ("ol", &["start"]),
+];
+
+const ALLOWED_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+fn sanitizer() -> Builder<'static> {
+    let attributes = ALLOWED_ATTRIBUTES.iter()
+        .map(|&(tag, attributes)| (tag, attributes.iter().cloned().collect::<HashSet<_>>()))
+        .collect::<HashMap<_, _>>();
+
+    let mut builder = Builder::default();
+    builder
+        .tags(ALLOWED_TAGS.iter().cloned().collect())
+        .tag_attributes(attributes)
+        .generic_attributes(HashSet::new())
+        .url_schemes(ALLOWED_URL_SCHEMES.iter().cloned().collect())
+        .link_rel(Some("noopener noreferrer nofollow"));
+    builder
+}
+
+/// Renders CommonMark, with tables and strikethrough, and sanitizes the result.
+pub fn render(markdown: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH));
+    sanitizer().clean(&unsafe_html).to_string()
+}
+
+/// Escapes plain text, such as a title, for use in HTML.
+pub fn escape(text: &str) -> String {
+    ammonia::clean_text(text)
+}
+
+/// How many rendered bodies a `RenderCache` keeps unless told otherwise.
+pub const DEFAULT_CAPACITY: usize = 1000;
+
+/// Rendered bodies kept in memory, keyed by post id and version. Every edit of a post bumps its
+/// version, so an entry never goes stale; it is only dropped once it is the least recently used
+/// of more than the cache's capacity, or when its post is deleted for good. Clones share the
+/// same entries, so the trash purger can evict what it deletes.
+#[derive(Clone)]
+pub struct RenderCache {
+    capacity: usize,
+    rendered: Arc<Mutex<Rendered>>,
+}
+
+#[derive(Default)]
+struct Rendered {
+    entries: HashMap<(i32, i32), Entry>,
+    /// Counts lookups, to tell which entry was used least recently.
+    clock: u64,
+}
+
+struct Entry {
+    html: String,
+    last_used: u64,
+}
+
+impl Default for RenderCache {
+    fn default() -> RenderCache {
+        RenderCache::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl RenderCache {
+    pub fn new() -> RenderCache {
+        RenderCache::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> RenderCache {
+        RenderCache { capacity, rendered: Arc::default() }
+    }
+
+    pub fn body_html(&self, post: &Post) -> String {
+        let key = (post.id, post.version);
+        {
+            let mut rendered = self.lock();
+            rendered.clock += 1;
+            let now = rendered.clock;
+            if let Some(entry) = rendered.entries.get_mut(&key) {
+                entry.last_used = now;
+                return entry.html.clone();
+            }
+        }
+
+        // Rendered without holding the lock, so a long post doesn't hold up everybody else.
+        let html = render(&post.body);
+        let mut rendered = self.lock();
+        let last_used = rendered.clock;
+        rendered.entries.insert(key, Entry { html: html.clone(), last_used });
+        if rendered.entries.len() > self.capacity {
+            let oldest = rendered.entries.iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(&key, _)| key);
+            if let Some(oldest) = oldest {
+                rendered.entries.remove(&oldest);
+            }
+        }
+        html
+    }
+
+    /// Forgets every version of the posts with `ids`.
+    pub fn evict(&self, ids: &[i32]) {
+        self.lock().entries.retain(|&(id, _), _| !ids.contains(&id));
+    }
+
+    fn lock(&self) -> MutexGuard<Rendered> {
+        self.rendered.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use crate::models::{Post, PostStatus};
+    use super::{render, RenderCache};
+
+    #[test]
+    fn markdown_is_rendered_and_sanitized() {
+        assert_eq!(render("Some *emphasis*"), "<p>Some <em>emphasis</em></p>\n");
+        assert!(render("```rust\nfn main() {}\n```").starts_with("<pre><code>fn main() {}"));
+        assert!(render("| a |\n|---|\n| b |").contains("<td>b</td>"));
+
+        let html = render("<script>alert(1)</script>\n\n[x](javascript:alert(1))");
+        assert!(!html.contains("script"));
+        assert!(!html.contains("javascript"));
+    }
+
+    fn post(id: i32, version: i32, body: &str) -> Post {
+        Post {
+            id,
+            title: String::from("Title"),
+            body: body.to_string(),
+            status: PostStatus::Published,
+            publish_at: None,
+            slug: String::from("title"),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            author_id: None,
+            version,
+        }
+    }
+
+    #[test]
+    fn the_cache_keeps_the_latest_versions_it_has_room_for() {
+        let cache = RenderCache::with_capacity(2);
+        assert_eq!(cache.body_html(&post(1, 1, "one")), "<p>one</p>\n");
+        // The same version is taken from the cache, even though this body is different.
+        assert_eq!(cache.body_html(&post(1, 1, "changed")), "<p>one</p>\n");
+        assert_eq!(cache.body_html(&post(1, 2, "changed")), "<p>changed</p>\n");
+
+        // Version 1 of post 1 was used least recently, so it makes room for post 2.
+        cache.body_html(&post(2, 1, "two"));
+        assert_eq!(cache.body_html(&post(1, 1, "again")), "<p>again</p>\n");
+
+        cache.evict(&[1]);
+        assert_eq!(cache.body_html(&post(1, 1, "evicted")), "<p>evicted</p>\n");
+        assert_eq!(cache.body_html(&post(2, 1, "kept")), "<p>two</p>\n");
+    }
+}
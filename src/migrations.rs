@@ -0,0 +1,102 @@
+//! The `migrations` directory, built into the binary so that the database is brought up to date
+//! at launch instead of by running `diesel setup` by hand on every machine. With the `sqlite`
+//! feature it is `migrations_sqlite` instead.
+
+use std::io;
+
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use diesel_migrations::RunMigrationsError;
+use serde::Serialize;
+
+use crate::connection_pool::DbConnection;
+
+#[cfg(not(feature = "sqlite"))]
+embed_migrations!();
+#[cfg(feature = "sqlite")]
+embed_migrations!("migrations_sqlite");
+
+// The directory names of the embedded migrations, in order, as `MIGRATION_NAMES`.
+include!(concat!(env!("OUT_DIR"), "/migrations.rs"));
+
+// Where Diesel records the migrations it has run.
+table! {
+    __diesel_schema_migrations (version) {
+        version -> VarChar,
+        run_on -> Timestamp,
+    }
+}
+
+/// A migration either built into the binary, recorded as run, or both.
+#[derive(Serialize)]
+pub struct Migration {
+    pub version: String,
+    /// `None` for migrations the database had run by some other build.
+    pub name: Option<String>,
+    pub run_on: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<Migration>,
+    pub pending: Vec<Migration>,
+}
+
+/// Splits a migration directory name into the version Diesel records it under and its name:
+/// `2020-05-02-194227_create_posts` is version `20200502194227`.
+fn parse(directory: &str) -> (String, &str) {
+    let mut parts = directory.splitn(2, '_');
+    let version = parts.next().unwrap_or_default().replace('-', "");
+    (version, parts.next().unwrap_or(""))
+}
+
+/// Runs the embedded migrations the database hasn't had yet, oldest first and each in a
+/// transaction of its own, printing each one as it goes.
+pub fn run_pending(connection: &DbConnection) -> Result<(), RunMigrationsError> {
+    embedded_migrations::run_with_output(connection, &mut io::stdout())
+}
+
+/// The migrations the database has had, in the order they were run, and the embedded ones it
+/// hasn't.
+pub fn status(connection: &DbConnection) -> QueryResult<MigrationStatus> {
+    use self::__diesel_schema_migrations::dsl::*;
+
+    let run = __diesel_schema_migrations
+        .select((version, run_on))
+        .order((run_on, version))
+        .load::<(String, NaiveDateTime)>(connection)?;
+    let embedded = MIGRATION_NAMES.iter().map(|directory| parse(directory)).collect::<Vec<_>>();
+
+    let applied = run.iter()
+        .map(|(applied_version, applied_on)| Migration {
+            version: applied_version.clone(),
+            name: embedded.iter()
+                .find(|(embedded_version, _)| embedded_version == applied_version)
+                .map(|(_, name)| name.to_string()),
+            run_on: Some(*applied_on),
+        })
+        .collect();
+    let pending = embedded.iter()
+        .filter(|(embedded_version, _)| !run.iter().any(|(applied_version, _)| applied_version == embedded_version))
+        .map(|(embedded_version, name)| Migration {
+            version: embedded_version.clone(),
+            name: Some(name.to_string()),
+            run_on: None,
+        })
+        .collect();
+    Ok(MigrationStatus { applied, pending })
+}
+
+#[cfg(test)]
+mod test {
+    use super::parse;
+
+    #[test]
+    fn versions_are_the_digits_before_the_name() {
+        assert_eq!(parse("2020-05-02-194227_create_posts"), (String::from("20200502194227"), "create_posts"));
+        assert_eq!(parse("00000000000000_diesel_initial_setup"),
+                   (String::from("00000000000000"), "diesel_initial_setup"));
+        #[cfg(not(feature = "sqlite"))]
+        assert_eq!(super::MIGRATION_NAMES.first(), Some(&"00000000000000_diesel_initial_setup"));
+    }
+}
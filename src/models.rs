@@ -1,16 +1,373 @@
-use super::schema::posts;
-use serde::{Serialize, Deserialize};
-#[derive(Queryable, AsChangeset, Serialize, Deserialize)]
+use super::schema::{comments, post_revisions, post_slugs, post_tags, posts, tags, users};
+use super::sql_types::{Comment_status, Post_status};
+use serde::{Serialize, Deserialize, Deserializer};
+use std::io::Write;
+use chrono::{DateTime, Utc};
+use diesel::backend::Backend;
+use diesel::deserialize::{self, FromSql};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel::serialize::{self, IsNull, Output, ToSql};
+use diesel::sql_types::{BigInt, Float4, Int4, Text};
+use rocket::request::FromFormValue;
+
+/// The columns a `Post` is loaded from. `posts` also has the generated `searchable` column,
+/// which can't be deserialized, so post queries select these explicitly instead of `*`.
+pub type PostColumns = (posts::id, posts::title, posts::body, posts::status, posts::publish_at, posts::slug,
+                        posts::created_at, posts::updated_at, posts::author_id, posts::version);
+pub const POST_COLUMNS: PostColumns = (posts::id, posts::title, posts::body, posts::status, posts::publish_at,
+                                       posts::slug, posts::created_at, posts::updated_at, posts::author_id,
+                                       posts::version);
+
+pub type LivePosts = diesel::dsl::Filter<posts::table, diesel::dsl::IsNull<posts::deleted_at>>;
+
+/// `posts` minus the ones sitting in the trash. Reads and edits start from here rather than
+/// from `posts::table`, so trashed posts stay out of sight until they are restored.
+pub fn live_posts() -> LivePosts {
+    posts::table.filter(posts::deleted_at.is_null())
+}
+
+/// Where a post is in its editorial lifecycle. Posts only ever move one step forwards:
+/// draft → review → published → archived.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, FromFormValue, Serialize, Deserialize)]
+#[sql_type = "Post_status"]
+#[serde(rename_all = "lowercase")]
+pub enum PostStatus {
+    Draft,
+    Review,
+    Published,
+    Archived,
+}
+
+impl PostStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PostStatus::Draft => "draft",
+            PostStatus::Review => "review",
+            PostStatus::Published => "published",
+            PostStatus::Archived => "archived",
+        }
+    }
+
+    /// The only status a post may move into this one from, other than this one itself.
+    pub fn previous(self) -> Option<PostStatus> {
+        match self {
+            PostStatus::Draft => None,
+            PostStatus::Review => Some(PostStatus::Draft),
+            PostStatus::Published => Some(PostStatus::Review),
+            PostStatus::Archived => Some(PostStatus::Published),
+        }
+    }
+
+    pub fn can_become(self, next: PostStatus) -> bool {
+        self == next || next.previous() == Some(self)
+    }
+}
+
+// The enum's labels on Postgres, and plain text on SQLite.
+impl<DB: Backend> ToSql<Post_status, DB> for PostStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl<DB: Backend> FromSql<Post_status, DB> for PostStatus where String: FromSql<Text, DB> {
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, DB>>::from_sql(bytes)?.as_str() {
+            "draft" => Ok(PostStatus::Draft),
+            "review" => Ok(PostStatus::Review),
+            "published" => Ok(PostStatus::Published),
+            "archived" => Ok(PostStatus::Archived),
+            _ => Err("Unrecognized post_status variant".into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(User, foreign_key = "author_id")]
+#[table_name="posts"]
 pub struct Post {
     pub id: i32,
     pub title: String,
     pub body: String,
-    pub published: bool,
+    pub status: PostStatus,
+    /// When the publisher should move this post from review to published.
+    pub publish_at: Option<DateTime<Utc>>,
+    /// Unique, URL friendly name derived from the title. Renaming a post changes it, and the
+    /// old slug is kept in `post_slugs` so links to it keep working.
+    pub slug: String,
+    pub created_at: DateTime<Utc>,
+    /// Bumped by the `set_updated_at` trigger whenever the row changes, including status changes
+    /// made by the publisher and moves in and out of the trash.
+    pub updated_at: DateTime<Utc>,
+    /// Who wrote the post, if anybody was signed in at the time. The API embeds the whole
+    /// author instead, see `PostDetails`.
+    #[serde(skip_serializing)]
+    pub author_id: Option<i32>,
+    /// Goes up by one with every write to the row, see `If-Match` on the edit routes.
+    pub version: i32,
 }
 
-#[derive(Insertable)]
+/// A post as the API hands it out: its own columns plus its author, the tags it is filed under
+/// and how many approved comments it has.
+#[derive(Debug, Serialize)]
+pub struct PostDetails {
+    #[serde(flatten)]
+    pub post: Post,
+    pub author: Option<User>,
+    pub tags: Vec<String>,
+    pub comment_count: i64,
+    /// The body rendered from Markdown, only there when asked for with `render=html`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_html: Option<String>,
+}
+
+/// A post sent by a client, along with what isn't a column to write: the tags to file it
+/// under, and for edits the version of the post they are based on.
+#[derive(Deserialize)]
+pub struct PostInput<T> {
+    #[serde(flatten)]
+    pub post: T,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub version: Option<i32>,
+}
+
+/// The slug isn't taken from the request, it's generated from the title before inserting, and
+/// the author is whoever is signed in.
+#[derive(Insertable, Deserialize)]
 #[table_name="posts"]
-pub struct NewPost<'a> {
+pub struct NewPost {
+    pub title: String,
+    pub body: String,
+    #[serde(skip_deserializing)]
+    pub slug: String,
+    #[serde(skip_deserializing)]
+    pub author_id: Option<i32>,
+}
+
+/// Every editable field of a post, as sent with a PUT. A missing `publish_at` clears the schedule.
+#[derive(Deserialize)]
+pub struct PostReplacement {
+    pub title: String,
+    pub body: String,
+    pub status: PostStatus,
+    pub publish_at: Option<DateTime<Utc>>,
+}
+
+/// Partial update of a post: only the fields that are present get written.
+#[derive(AsChangeset, Deserialize)]
+#[table_name="posts"]
+pub struct PostChanges {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub status: Option<PostStatus>,
+    /// Absent leaves the schedule alone, `null` clears it.
+    #[serde(default, deserialize_with = "present")]
+    pub publish_at: Option<Option<DateTime<Utc>>>,
+}
+
+/// A PUT is saved as a PATCH that sets every field.
+impl From<PostReplacement> for PostChanges {
+    fn from(post: PostReplacement) -> PostChanges {
+        PostChanges {
+            title: Some(post.title),
+            body: Some(post.body),
+            status: Some(post.status),
+            publish_at: Some(post.publish_at),
+        }
+    }
+}
+
+impl PostChanges {
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.body.is_none() && self.status.is_none() && self.publish_at.is_none()
+    }
+}
+
+/// Deserializes a field that is present in the JSON, even as `null`, into `Some`. Together with
+/// `#[serde(default)]` this tells an absent field apart from an explicit `null`.
+fn present<'de, T, D>(deserializer: D) -> Result<Option<T>, D::Error>
+    where T: Deserialize<'de>, D: Deserializer<'de>
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+/// A post in the trash, loaded from `(POST_COLUMNS, posts::deleted_at)`.
+#[derive(Queryable, Serialize)]
+pub struct TrashedPost {
+    #[serde(flatten)]
+    pub post: Post,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// The title and body a post had before an edit, kept so that edits can be reviewed and undone.
+#[derive(Queryable, Serialize)]
+pub struct PostRevision {
+    pub id: i32,
+    pub post_id: i32,
+    pub title: String,
+    pub body: String,
+    pub editor: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[table_name="post_revisions"]
+pub struct NewPostRevision<'a> {
+    pub post_id: i32,
     pub title: &'a str,
     pub body: &'a str,
-}
\ No newline at end of file
+    pub editor: Option<&'a str>,
+}
+
+/// A slug a post used to have, kept so that old links redirect to where the post lives now.
+#[derive(Insertable)]
+#[table_name="post_slugs"]
+pub struct OldSlug<'a> {
+    pub slug: &'a str,
+    pub post_id: i32,
+}
+
+#[derive(Debug, Clone, Identifiable, Queryable, Serialize)]
+#[table_name="users"]
+pub struct User {
+    pub id: i32,
+    pub username: String,
+    pub display_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Identifiable, Queryable, Serialize)]
+#[table_name="tags"]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Identifiable, Queryable, Associations, Insertable)]
+#[belongs_to(Post)]
+#[belongs_to(Tag)]
+#[table_name="post_tags"]
+#[primary_key(post_id, tag_id)]
+pub struct PostTag {
+    pub post_id: i32,
+    pub tag_id: i32,
+}
+
+/// A tag and how many posts outside the trash are filed under it.
+#[derive(QueryableByName, Serialize)]
+pub struct TagCount {
+    #[sql_type = "Text"]
+    pub name: String,
+    #[sql_type = "BigInt"]
+    pub posts: i64,
+}
+
+/// Where a comment is in moderation. New comments wait as pending until an editor approves
+/// them or marks them as spam; only approved ones are shown to readers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, AsExpression, FromSqlRow, FromFormValue, Serialize, Deserialize)]
+#[sql_type = "Comment_status"]
+#[serde(rename_all = "lowercase")]
+pub enum CommentStatus {
+    Pending,
+    Approved,
+    Spam,
+}
+
+impl CommentStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CommentStatus::Pending => "pending",
+            CommentStatus::Approved => "approved",
+            CommentStatus::Spam => "spam",
+        }
+    }
+}
+
+impl<DB: Backend> ToSql<Comment_status, DB> for CommentStatus {
+    fn to_sql<W: Write>(&self, out: &mut Output<W, DB>) -> serialize::Result {
+        out.write_all(self.as_str().as_bytes())?;
+        Ok(IsNull::No)
+    }
+}
+
+impl<DB: Backend> FromSql<Comment_status, DB> for CommentStatus where String: FromSql<Text, DB> {
+    fn from_sql(bytes: Option<&DB::RawValue>) -> deserialize::Result<Self> {
+        match <String as FromSql<Text, DB>>::from_sql(bytes)?.as_str() {
+            "pending" => Ok(CommentStatus::Pending),
+            "approved" => Ok(CommentStatus::Approved),
+            "spam" => Ok(CommentStatus::Spam),
+            _ => Err("Unrecognized comment_status variant".into()),
+        }
+    }
+}
+
+/// A comment on a post, or a reply to another comment on the same post when it has a parent.
+#[derive(Identifiable, Queryable, Associations, Serialize)]
+#[belongs_to(Post)]
+#[table_name="comments"]
+pub struct Comment {
+    pub id: i32,
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    pub author: String,
+    pub body: String,
+    pub status: CommentStatus,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The post comes from the URL, and every new comment starts out pending.
+#[derive(Insertable, Deserialize)]
+#[table_name="comments"]
+pub struct NewComment {
+    #[serde(skip_deserializing)]
+    pub post_id: i32,
+    pub parent_id: Option<i32>,
+    pub author: String,
+    pub body: String,
+}
+
+/// A comment with the replies to it, and the replies to those, nested underneath.
+#[derive(Serialize)]
+pub struct CommentNode {
+    #[serde(flatten)]
+    pub comment: Comment,
+    pub replies: Vec<CommentNode>,
+}
+
+#[derive(Deserialize)]
+pub struct CommentModeration {
+    pub status: CommentStatus,
+}
+
+/// A full-text search hit, ranked against the query, with the matching parts of the body
+/// wrapped in `<mark>` tags.
+#[derive(QueryableByName, Serialize)]
+pub struct SearchResult {
+    #[sql_type = "Int4"]
+    pub id: i32,
+    #[sql_type = "Text"]
+    pub title: String,
+    #[sql_type = "Float4"]
+    pub rank: f32,
+    #[sql_type = "Text"]
+    pub snippet: String,
+}
+
+#[cfg(test)]
+mod test {
+    use super::PostStatus::*;
+
+    #[test]
+    fn status_moves_one_step_forwards() {
+        assert!(Draft.can_become(Review));
+        assert!(Review.can_become(Published));
+        assert!(Published.can_become(Archived));
+        assert!(Review.can_become(Review));
+        assert!(!Draft.can_become(Published));
+        assert!(!Published.can_become(Draft));
+        assert!(!Archived.can_become(Published));
+    }
+}
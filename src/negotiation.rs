@@ -0,0 +1,204 @@
+//! Content negotiation for listings: the same page of items as JSON, CSV, MessagePack or an
+//! HTML table, picked by the `Accept` header or by the extension of the route.
+
+use rocket::http::{Accept, ContentType, MediaType, Status};
+use rocket::request::Request;
+use rocket::response::{self, content, Responder};
+use rocket_contrib::json::Json;
+use serde::Serialize;
+
+use crate::markdown;
+use crate::models::PostDetails;
+use crate::pagination::Page;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Csv,
+    MessagePack,
+    Html,
+}
+
+impl Format {
+    /// The format served for `media_type`; `*/*` gets JSON, as do clients without `Accept`.
+    fn of(media_type: &MediaType) -> Option<Format> {
+        let (top, sub) = (media_type.top(), media_type.sub());
+        if top == "*" && sub == "*" || top == "application" && (sub == "json" || sub == "*") {
+            Some(Format::Json)
+        } else if top == "text" && sub == "csv" {
+            Some(Format::Csv)
+        } else if top == "application" && (sub == "msgpack" || sub == "x-msgpack") {
+            Some(Format::MessagePack)
+        } else if top == "text" && (sub == "html" || sub == "*") {
+            Some(Format::Html)
+        } else {
+            None
+        }
+    }
+}
+
+/// The format the client weighs highest among those served, earlier ones winning ties, or
+/// `None` when it accepts none of them.
+pub fn negotiate(accept: Option<&Accept>) -> Option<Format> {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return Some(Format::Json),
+    };
+
+    let mut best: Option<(f32, Format)> = None;
+    for media_type in accept.iter() {
+        let weight = media_type.weight_or(1.0);
+        if weight <= 0.0 {
+            continue;
+        }
+        if let Some(format) = Format::of(media_type.media_type()) {
+            if best.map_or(true, |(best, _)| weight > best) {
+                best = Some((weight, format));
+            }
+        }
+    }
+    best.map(|(_, format)| format)
+}
+
+/// Items that can be laid out as the rows of a table, for CSV and HTML.
+pub trait Tabular {
+    /// What a table of these is called, such as the title of the HTML page.
+    const NAME: &'static str;
+
+    fn columns() -> &'static [&'static str];
+    fn cells(&self) -> Vec<String>;
+}
+
+impl Tabular for PostDetails {
+    const NAME: &'static str = "Posts";
+
+    fn columns() -> &'static [&'static str] {
+        &["id", "title", "slug", "status", "publish_at", "created_at", "updated_at", "version", "author",
+          "tags", "comment_count", "body"]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        let post = &self.post;
+        vec![
+            post.id.to_string(),
+            post.title.clone(),
+            post.slug.clone(),
+            post.status.as_str().to_string(),
+            post.publish_at.map(|publish_at| publish_at.to_rfc3339()).unwrap_or_default(),
+            post.created_at.to_rfc3339(),
+            post.updated_at.to_rfc3339(),
+            post.version.to_string(),
+            self.author.as_ref().map(|author| author.username.clone()).unwrap_or_default(),
+            self.tags.join(", "),
+            self.comment_count.to_string(),
+            post.body.clone(),
+        ]
+    }
+}
+
+/// Quotes a CSV field when it has to be, doubling any quotes inside it, as RFC 4180 has it.
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| c == ',' || c == '"' || c == '\n' || c == '\r') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_line<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields.iter().map(|field| csv_field(field.as_ref())).collect::<Vec<_>>().join(",");
+    line.push_str("\r\n");
+    line
+}
+
+pub fn csv<T: Tabular>(items: &[T]) -> String {
+    let mut csv = csv_line(T::columns());
+    for item in items {
+        csv.push_str(&csv_line(&item.cells()));
+    }
+    csv
+}
+
+pub fn html_table<T: Tabular>(page: &Page<T>) -> String {
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head><meta charset=\"utf-8\"><title>{}</title></head>\n<body>\n<table>\n<thead><tr>",
+                           T::NAME);
+    for column in T::columns() {
+        html.push_str(&format!("<th>{}</th>", column));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+    for item in &page.items {
+        html.push_str("<tr>");
+        for cell in item.cells() {
+            html.push_str(&format!("<td>{}</td>", markdown::escape(&cell)));
+        }
+        html.push_str("</tr>\n");
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    if let Some(ref prev) = page.prev {
+        html.push_str(&format!("<a href=\"{}\" rel=\"prev\">Previous</a>\n", markdown::escape(prev)));
+    }
+    if let Some(ref next) = page.next {
+        html.push_str(&format!("<a href=\"{}\" rel=\"next\">Next</a>\n", markdown::escape(next)));
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// A page of items in the format the client asked for. `format` is fixed by routes with an
+/// extension such as `/posts.csv`; when it is `None` the `Accept` header decides, answering 406
+/// if none of the formats will do.
+pub struct Negotiated<T> {
+    pub format: Option<Format>,
+    pub page: Page<T>,
+}
+
+impl<'r, T: Serialize + Tabular> Responder<'r> for Negotiated<T> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let format = match self.format {
+            Some(format) => format,
+            None => negotiate(request.accept()).ok_or(Status::NotAcceptable)?,
+        };
+
+        let link = self.page.link();
+        let mut response = match format {
+            Format::Json => Json(&self.page.items).respond_to(request)?,
+            Format::Csv => content::Content(ContentType::with_params("text", "csv", ("charset", "utf-8")),
+                                            csv(&self.page.items)).respond_to(request)?,
+            Format::MessagePack => {
+                let bytes = rmp_serde::to_vec_named(&self.page.items).map_err(|error| {
+                    println!("MessagePack error: {}", error);
+                    Status::InternalServerError
+                })?;
+                content::Content(ContentType::new("application", "msgpack"), bytes).respond_to(request)?
+            }
+            Format::Html => content::Html(html_table(&self.page)).respond_to(request)?,
+        };
+        if let Some(link) = link {
+            response.set_raw_header("Link", link);
+        }
+        if self.format.is_none() {
+            response.set_raw_header("Vary", "Accept");
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accept_picks_the_heaviest_served_format() {
+        let accept = |header: &str| header.parse::<Accept>().unwrap();
+        assert_eq!(negotiate(None), Some(Format::Json));
+        assert_eq!(negotiate(Some(&accept("text/csv;q=0.5, application/msgpack"))), Some(Format::MessagePack));
+        assert_eq!(negotiate(Some(&accept("text/html, */*;q=0.8"))), Some(Format::Html));
+        assert_eq!(negotiate(Some(&accept("image/png"))), None);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_line(&["1", "Hello, \"World\"", "plain"]), "1,\"Hello, \"\"World\"\"\",plain\r\n");
+    }
+}
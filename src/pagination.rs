@@ -0,0 +1,88 @@
+use rocket::http::uri::Origin;
+use rocket::request::Request;
+use rocket::response::{self, Responder};
+use rocket_contrib::json::Json;
+use serde::Serialize;
+
+pub const DEFAULT_LIMIT: i64 = 20;
+pub const MAX_LIMIT: i64 = 100;
+
+/// Clamps a client supplied `limit` to `1..=MAX_LIMIT`, falling back to `DEFAULT_LIMIT`.
+pub fn page_size(limit: Option<i64>) -> i64 {
+    limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT)
+}
+
+/// One page of a keyset paginated listing. The items are serialized as a plain JSON array and
+/// the neighbouring pages are advertised through an RFC 8288 `Link` header.
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// Builds the URI of a neighbouring page: the current request's query string with `limit`,
+/// `after` and `before` swapped for the given cursor, so any other filters carry over.
+pub fn cursor_uri(origin: &Origin, limit: i64, cursor: &str, id: i32) -> String {
+    let mut params: Vec<&str> = origin.query()
+        .map(|query| query.split('&')
+            .filter(|pair| {
+                let name = pair.split('=').next().unwrap_or("");
+                !name.is_empty() && name != "limit" && name != "after" && name != "before"
+            })
+            .collect())
+        .unwrap_or_default();
+
+    let limit = format!("limit={}", limit);
+    let cursor = format!("{}={}", cursor, id);
+    params.push(&limit);
+    params.push(&cursor);
+    format!("{}?{}", origin.path(), params.join("&"))
+}
+
+impl<T> Page<T> {
+    /// The `Link` header pointing at the neighbouring pages, if there are any.
+    pub fn link(&self) -> Option<String> {
+        let mut links = Vec::new();
+        if let Some(ref next) = self.next {
+            links.push(format!("<{}>; rel=\"next\"", next));
+        }
+        if let Some(ref prev) = self.prev {
+            links.push(format!("<{}>; rel=\"prev\"", prev));
+        }
+
+        if links.is_empty() {
+            None
+        } else {
+            Some(links.join(", "))
+        }
+    }
+}
+
+impl<'r, T: Serialize> Responder<'r> for Page<T> {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let link = self.link();
+        let mut response = Json(self.items).respond_to(request)?;
+        if let Some(link) = link {
+            response.set_raw_header("Link", link);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn cursor_uri_keeps_other_params() {
+        let origin = Origin::parse("/posts?published=true&after=3&limit=50").unwrap();
+        assert_eq!(cursor_uri(&origin, 10, "after", 42), "/posts?published=true&limit=10&after=42");
+    }
+
+    #[test]
+    fn page_size_is_clamped() {
+        assert_eq!(page_size(None), DEFAULT_LIMIT);
+        assert_eq!(page_size(Some(0)), 1);
+        assert_eq!(page_size(Some(1000)), MAX_LIMIT);
+    }
+}
@@ -0,0 +1,48 @@
+//! Background worker that publishes posts once their `publish_at` time has come.
+
+use std::time::Duration;
+
+use diesel::prelude::*;
+
+use crate::background;
+use crate::connection_pool::{DbConnection, Pool};
+
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+// Every app instance runs a publisher. `FOR UPDATE SKIP LOCKED` lets each of them claim a
+// disjoint batch of due posts instead of queueing up behind whichever instance got there first.
+#[cfg(not(feature = "sqlite"))]
+const PUBLISH_DUE_SQL: &str = "UPDATE posts SET status = 'published' \
+    WHERE id IN ( \
+        SELECT id FROM posts \
+        WHERE status = 'review' AND publish_at <= now() AND deleted_at IS NULL \
+        ORDER BY publish_at \
+        LIMIT 100 \
+        FOR UPDATE SKIP LOCKED \
+    )";
+// SQLite takes the whole database for a write, so there is nothing to skip.
+#[cfg(feature = "sqlite")]
+const PUBLISH_DUE_SQL: &str = "UPDATE posts SET status = 'published' \
+    WHERE id IN ( \
+        SELECT id FROM posts \
+        WHERE status = 'review' AND publish_at <= strftime('%Y-%m-%d %H:%M:%f', 'now') AND deleted_at IS NULL \
+        ORDER BY publish_at \
+        LIMIT 100 \
+    )";
+
+/// Moves up to 100 posts in review whose `publish_at` has passed to published, the ones due
+/// longest first, returning how many posts were published. Any more are left for the next run.
+pub fn publish_due_posts(connection: &DbConnection) -> QueryResult<usize> {
+    diesel::sql_query(PUBLISH_DUE_SQL).execute(connection)
+}
+
+/// Starts the publisher thread, which checks for due posts every `interval`.
+pub fn spawn(pool: Pool, interval: Duration) {
+    background::spawn_periodic("post-publisher", pool, interval, |connection| {
+        match publish_due_posts(connection) {
+            Ok(0) => {}
+            Ok(count) => println!("Published {} scheduled posts", count),
+            Err(error) => println!("Error publishing scheduled posts: {}", error),
+        }
+    });
+}
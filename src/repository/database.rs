@@ -0,0 +1,467 @@
+//! `PostRepository` on the database, through Diesel and a pool of connections: Postgres, or
+//! SQLite with the `sqlite` feature. Listings and lookups read from the replicas when there are
+//! any, so a post may show up there a moment after it was saved; edits always go to the
+//! primary, and answer with the post as it is there.
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::result::Error;
+use diesel::sql_types::Bool;
+
+use crate::authors;
+use crate::connection_pool::{DbBackend, DbConnection, Pool, PooledConnection, Replicas};
+use crate::dialect::{self, like_pattern};
+use crate::models::{live_posts, NewPost, NewPostRevision, Post, PostChanges, PostDetails, PostStatus, POST_COLUMNS};
+use crate::schema::{post_revisions, post_slugs, post_tags, posts, tags};
+use crate::slugs;
+use crate::tagging;
+use crate::threads;
+use super::{Cursor, PostFilter, PostRepository, PostSort, RepositoryError, Slice, SlugMatch};
+
+/// Orders `$query` by `($column, id)` and, given a cursor, keeps only the posts on the far side
+/// of it. The cursor is a post id, so its `$column` value is looked up first; an id that doesn't
+/// name a live post is an invalid cursor.
+macro_rules! keyset {
+    ($query:expr, $column:expr, $key:ty, $cursor:expr, $ascending:expr, $connection:expr) => {{
+        let mut query = $query;
+        if let Some(cursor) = $cursor {
+            let key = live_posts().filter(posts::id.eq(cursor))
+                .select($column)
+                .first::<$key>($connection)
+                .map_err(|error| match error {
+                    Error::NotFound => RepositoryError::InvalidCursor(cursor),
+                    error => RepositoryError::from(error),
+                })?;
+            let (past, at) = (dialect::bind($column, key.clone()), dialect::bind($column, key));
+            query = if $ascending {
+                query.filter($column.gt(past).or($column.eq(at).and(posts::id.gt(cursor))))
+            } else {
+                query.filter($column.lt(past).or($column.eq(at).and(posts::id.lt(cursor))))
+            };
+        }
+        if $ascending {
+            query.order(($column.asc(), posts::id.asc()))
+        } else {
+            query.order(($column.desc(), posts::id.desc()))
+        }
+    }};
+}
+
+pub struct DatabasePostRepository {
+    pool: Pool,
+    replicas: Replicas,
+}
+
+impl DatabasePostRepository {
+    pub fn new(pool: Pool, replicas: Replicas) -> DatabasePostRepository {
+        DatabasePostRepository { pool, replicas }
+    }
+
+    fn connection(&self) -> Result<PooledConnection, RepositoryError> {
+        self.pool.get().map_err(|error| {
+            println!("Database pool error: {}", error);
+            RepositoryError::Unavailable
+        })
+    }
+
+    /// A replica to read from, or the primary when none of them can be reached.
+    fn read_connection(&self) -> Result<PooledConnection, RepositoryError> {
+        match self.replicas.get() {
+            Some(connection) => Ok(connection),
+            None => self.connection(),
+        }
+    }
+}
+
+/// Pairs each of `posts` with its author, tags and comment count.
+pub fn with_details(connection: &DbConnection, posts: Vec<Post>) -> QueryResult<Vec<PostDetails>> {
+    let authors = authors::for_posts(connection, &posts)?;
+    let tags = tagging::for_posts(connection, &posts)?;
+    let comment_counts = threads::approved_counts(connection, &posts)?;
+    Ok(posts.into_iter()
+        .zip(authors)
+        .zip(tags)
+        .zip(comment_counts)
+        .map(|(((post, author), tags), comment_count)| PostDetails {
+            post,
+            author,
+            tags,
+            comment_count,
+            body_html: None,
+        })
+        .collect())
+}
+
+fn details(connection: &DbConnection, post: Post) -> QueryResult<PostDetails> {
+    with_details(connection, vec![post]).map(|mut details| details.remove(0))
+}
+
+fn find_post(connection: &DbConnection, id: i32) -> QueryResult<Post> {
+    live_posts().filter(posts::id.eq(id))
+        .select(POST_COLUMNS)
+        .first::<Post>(connection)
+}
+
+type UpdateGuard = Box<dyn BoxableExpression<posts::table, DbBackend, SqlType = Bool>>;
+
+/// Restricts an update to posts that may move into `status`, so the lifecycle is enforced by
+/// the `UPDATE` itself rather than by a separate read that could race with another editor.
+fn may_become(status: Option<PostStatus>) -> UpdateGuard {
+    match status {
+        Some(status) => Box::new(posts::status.eq(status)
+            .or(posts::status.eq(status.previous().unwrap_or(status)))),
+        None => Box::new(sql::<Bool>("TRUE")),
+    }
+}
+
+/// Restricts an update to the version of the post the edit was based on, so that it can't
+/// silently overwrite an edit somebody else saved in the meantime.
+fn is_version(version: Option<i32>) -> UpdateGuard {
+    match version {
+        Some(version) => Box::new(posts::version.eq(version)),
+        None => Box::new(sql::<Bool>("TRUE")),
+    }
+}
+
+/// Explains why a guarded update touched no rows: the post is gone, the edit was based on an
+/// outdated version, or the post can't move into `status` from where it currently is.
+fn rejected_update(connection: &DbConnection, id: i32, version: Option<i32>, status: Option<PostStatus>) -> RepositoryError {
+    let current = match find_post(connection, id) {
+        Ok(current) => current,
+        Err(error) => return error.into(),
+    };
+
+    if version.map_or(false, |version| version != current.version) {
+        return match details(connection, current) {
+            Ok(current) => RepositoryError::Outdated(Box::new(current)),
+            Err(error) => error.into(),
+        };
+    }
+    match status {
+        Some(to) => RepositoryError::Transition { id, from: current.status, to },
+        None => RepositoryError::NotFound,
+    }
+}
+
+/// Files `post` under `tags`, when there are any to file it under. Tags aren't a column of
+/// `posts`, so the trigger doesn't notice them changing and `updated_at` is bumped by hand.
+fn retag(connection: &DbConnection, post: Option<Post>, tags: Option<&[String]>) -> QueryResult<Option<Post>> {
+    let (post, tags) = match (post, tags) {
+        (Some(post), Some(tags)) => (post, tags),
+        (post, _) => return Ok(post),
+    };
+    if !tagging::replace(connection, post.id, tags)? {
+        return Ok(Some(post));
+    }
+
+    let updated = diesel::update(posts::table.filter(posts::id.eq(post.id)))
+        .set(posts::updated_at.eq(dialect::now()))
+        .execute(connection)?;
+    updated_post(connection, post.id, updated)
+}
+
+/// Post `id` as an update that changed `updated` rows left it, or `None` if it changed none.
+pub fn updated_post(connection: &DbConnection, id: i32, updated: usize) -> QueryResult<Option<Post>> {
+    if updated == 0 {
+        return Ok(None);
+    }
+    find_post(connection, id).map(Some)
+}
+
+/// Runs `update` against post `id` and, if it changed the title or body, keeps what they were
+/// before as a revision credited to `editor`. A new title also gets the post a new slug. The
+/// post stays locked from the read to the revision insert so that concurrent edits each record
+/// the version they actually replaced.
+pub fn with_revision<F>(id: i32, editor: Option<&str>, connection: &DbConnection, update: F) -> QueryResult<Option<Post>>
+    where F: FnOnce() -> QueryResult<Option<Post>>
+{
+    dialect::write_transaction(connection, || {
+        let previous = live_posts().filter(posts::id.eq(id))
+            .select((posts::title, posts::body));
+        #[cfg(not(feature = "sqlite"))]
+        let previous = previous.for_update();
+        let previous = previous.first::<(String, String)>(connection).optional()?;
+        let (title, body) = match previous {
+            Some(previous) => previous,
+            None => return Ok(None),
+        };
+
+        let mut updated = update()?;
+        if let Some(ref mut post) = updated {
+            if post.title != title {
+                slugs::rename(connection, id, &post.slug, &post.title)?;
+                // The rename is a write of its own, and moves the post on another version.
+                *post = find_post(connection, id)?;
+            }
+            if post.title != title || post.body != body {
+                diesel::insert_into(post_revisions::table)
+                    .values(&NewPostRevision { post_id: id, title: &title, body: &body, editor })
+                    .execute(connection)?;
+            }
+        }
+        Ok(updated)
+    })
+}
+
+impl PostRepository for DatabasePostRepository {
+    fn list(&self, filter: &PostFilter, sort: PostSort, cursor: Option<Cursor>, limit: i64) -> Result<Slice, RepositoryError> {
+        let connection = self.read_connection()?;
+        let connection = &*connection;
+        let forwards = cursor.map_or(true, Cursor::is_forwards);
+        let cursor = cursor.map(Cursor::id);
+        let ascending = forwards != sort.is_descending();
+
+        let mut query = live_posts().select(POST_COLUMNS).into_boxed();
+        if let Some(status) = filter.status {
+            query = query.filter(posts::status.eq(status));
+        }
+        if let Some(ref fragment) = filter.title_contains {
+            #[cfg(not(feature = "sqlite"))]
+            let matches = posts::title.ilike(like_pattern(fragment));
+            // SQLite's `LIKE` already ignores case, but only escapes anything when told how to.
+            #[cfg(feature = "sqlite")]
+            let matches = posts::title.like(like_pattern(fragment)).escape('\\');
+            query = query.filter(matches);
+        }
+        if let Some(ref tag) = filter.tag {
+            query = query.filter(posts::id.eq_any(post_tags::table
+                .inner_join(tags::table)
+                .filter(tags::name.eq(tag.as_str()))
+                .select(post_tags::post_id)));
+        }
+        if let Some(author) = filter.author {
+            query = query.filter(posts::author_id.eq(author));
+        }
+        if let Some(created_after) = filter.created_after {
+            query = query.filter(posts::created_at.gt(dialect::bind(posts::created_at, created_after)));
+        }
+        if let Some(created_before) = filter.created_before {
+            query = query.filter(posts::created_at.lt(dialect::bind(posts::created_at, created_before)));
+        }
+        if let Some(updated_after) = filter.updated_after {
+            query = query.filter(posts::updated_at.gt(dialect::bind(posts::updated_at, updated_after)));
+        }
+        if let Some(updated_before) = filter.updated_before {
+            query = query.filter(posts::updated_at.lt(dialect::bind(posts::updated_at, updated_before)));
+        }
+
+        query = match sort {
+            PostSort::Id | PostSort::IdDesc => {
+                if let Some(cursor) = cursor {
+                    query = if ascending {
+                        query.filter(posts::id.gt(cursor))
+                    } else {
+                        query.filter(posts::id.lt(cursor))
+                    };
+                }
+                if ascending {
+                    query.order(posts::id.asc())
+                } else {
+                    query.order(posts::id.desc())
+                }
+            }
+            PostSort::Title =>
+                keyset!(query, posts::title, String, cursor, ascending, connection),
+            PostSort::CreatedAt | PostSort::CreatedAtDesc =>
+                keyset!(query, posts::created_at, DateTime<Utc>, cursor, ascending, connection),
+            PostSort::UpdatedAt | PostSort::UpdatedAtDesc =>
+                keyset!(query, posts::updated_at, DateTime<Utc>, cursor, ascending, connection),
+        };
+
+        // Fetch one extra row to find out whether there is anything beyond this page.
+        let mut items = query.limit(limit + 1).load::<Post>(connection)?;
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+        if !forwards {
+            items.reverse();
+        }
+        Ok(Slice { items: with_details(connection, items)?, has_more })
+    }
+
+    fn find(&self, id: i32) -> Result<PostDetails, RepositoryError> {
+        let connection = self.read_connection()?;
+        let connection = &*connection;
+        let post = find_post(connection, id)?;
+        Ok(details(connection, post)?)
+    }
+
+    fn find_by_slug(&self, slug: &str) -> Result<SlugMatch, RepositoryError> {
+        let connection = self.read_connection()?;
+        let connection = &*connection;
+        let post = live_posts().filter(posts::slug.eq(slug))
+            .select(POST_COLUMNS)
+            .first::<Post>(connection)
+            .optional()?;
+        if let Some(post) = post {
+            return Ok(SlugMatch::Current(details(connection, post)?));
+        }
+
+        let current = post_slugs::table
+            .inner_join(posts::table)
+            .filter(post_slugs::slug.eq(slug))
+            .filter(posts::deleted_at.is_null())
+            .select(posts::slug)
+            .first::<String>(connection)?;
+        Ok(SlugMatch::Former(current))
+    }
+
+    fn create(&self, mut post: NewPost, tags: &[String], author: Option<&str>) -> Result<PostDetails, RepositoryError> {
+        let connection = self.connection()?;
+        let connection = &*connection;
+        post.slug = slugs::unique_slug(connection, &post.title, None)?;
+        post.author_id = match author {
+            Some(username) => authors::find_id(connection, username)?,
+            None => None,
+        };
+
+        let post = connection.transaction::<_, Error, _>(|| {
+            diesel::insert_into(posts::table).values(&post).execute(connection)?;
+            let id = dialect::last_insert_id(connection)?;
+            tagging::replace(connection, id, tags)?;
+            find_post(connection, id)
+        })?;
+        Ok(details(connection, post)?)
+    }
+
+    fn update(&self, id: i32, changes: &PostChanges, tags: Option<&[String]>, version: Option<i32>,
+              editor: Option<&str>) -> Result<PostDetails, RepositoryError> {
+        let connection = self.connection()?;
+        let connection = &*connection;
+        let updated = with_revision(id, editor, connection, || {
+            let updated = if changes.is_empty() {
+                // Only the tags change; an empty changeset is an error in Diesel.
+                live_posts().filter(posts::id.eq(id))
+                    .filter(is_version(version))
+                    .select(POST_COLUMNS)
+                    .first::<Post>(connection)
+                    .optional()?
+            } else {
+                let updated = diesel::update(live_posts().filter(posts::id.eq(id))
+                        .filter(is_version(version))
+                        .filter(may_become(changes.status)))
+                    .set(changes)
+                    .execute(connection)?;
+                updated_post(connection, id, updated)?
+            };
+            retag(connection, updated, tags)
+        })?;
+
+        match updated {
+            Some(post) => Ok(details(connection, post)?),
+            None => Err(rejected_update(connection, id, version, changes.status)),
+        }
+    }
+
+    fn trash(&self, id: i32, version: Option<i32>) -> Result<(), RepositoryError> {
+        let connection = self.connection()?;
+        let connection = &*connection;
+        let trashed = diesel::update(live_posts().filter(posts::id.eq(id)).filter(is_version(version)))
+            .set(posts::deleted_at.eq(dialect::now().nullable()))
+            .execute(connection)?;
+
+        if trashed == 0 {
+            Err(rejected_update(connection, id, version, None))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, not(feature = "sqlite")))]
+mod test {
+    use std::env;
+
+    use diesel::prelude::*;
+
+    use crate::models::{live_posts, NewPost, Post, POST_COLUMNS};
+    use crate::schema::posts;
+    use super::with_revision;
+
+    fn edit(connection: &PgConnection, id: i32, version: i32, title: &str) -> QueryResult<Option<Post>> {
+        with_revision(id, None, connection, || {
+            diesel::update(live_posts().filter(posts::id.eq(id)).filter(posts::version.eq(version)))
+                .set(posts::title.eq(title))
+                .returning(POST_COLUMNS)
+                .get_result::<Post>(connection)
+                .optional()
+        })
+    }
+
+    #[test]
+    #[ignore] // needs DATABASE_URL
+    fn a_retitled_post_can_be_edited_again_with_the_returned_version() {
+        let connection = PgConnection::establish(&env::var("DATABASE_URL").unwrap()).unwrap();
+        connection.test_transaction::<_, diesel::result::Error, _>(|| {
+            let post = diesel::insert_into(posts::table)
+                .values(&NewPost {
+                    title: "Hello".to_string(),
+                    body: "Body".to_string(),
+                    slug: "hello".to_string(),
+                    author_id: None,
+                })
+                .returning(POST_COLUMNS)
+                .get_result::<Post>(&connection)?;
+
+            let retitled = edit(&connection, post.id, post.version, "Hello again")?.expect("post exists");
+            assert_eq!(retitled.slug, "hello-again");
+
+            let edited = edit(&connection, post.id, retitled.version, "Hello once more")?;
+            assert_eq!(edited.map(|post| post.slug), Some("hello-once-more".to_string()));
+            Ok(())
+        });
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod sqlite_test {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::connection_pool::{self, PoolConfig};
+    use crate::migrations;
+
+    fn repository() -> DatabasePostRepository {
+        let config = PoolConfig {
+            url: String::from(":memory:"),
+            pool_size: 1,
+            min_idle: None,
+            connection_timeout: Duration::from_secs(5),
+            max_lifetime: None,
+            replica_urls: Vec::new(),
+        };
+        let pool = connection_pool::connect(&config).unwrap();
+        migrations::run_pending(&pool.get().unwrap()).unwrap();
+        DatabasePostRepository::new(pool, Replicas::connect(&config))
+    }
+
+    fn new_post(title: &str) -> NewPost {
+        NewPost { title: title.to_string(), body: String::from("Body"), slug: String::new(), author_id: None }
+    }
+
+    #[test]
+    fn edits_bump_the_version_and_keep_old_slugs() {
+        let repository = repository();
+        let post = repository.create(new_post("Hello"), &[String::from("rust")], Some("sk")).unwrap();
+        let id = post.post.id;
+        assert_eq!(post.post.version, 1);
+        assert_eq!(post.tags, vec!["rust"]);
+        assert_eq!(post.author.map(|author| author.username), Some(String::from("sk")));
+
+        let retitle = PostChanges { title: Some(String::from("Hello again")), body: None, status: None, publish_at: None };
+        let edited = repository.update(id, &retitle, None, Some(1), None).unwrap();
+        assert_eq!(edited.post.slug, "hello-again");
+        match repository.update(id, &retitle, None, Some(1), None) {
+            Err(RepositoryError::Outdated(current)) => assert_eq!(current.post.version, edited.post.version),
+            _ => panic!("an outdated edit was applied"),
+        }
+        match repository.find_by_slug("hello") {
+            Ok(SlugMatch::Former(slug)) => assert_eq!(slug, "hello-again"),
+            _ => panic!("the old slug doesn't redirect"),
+        }
+
+        let rewrite = PostChanges { title: None, body: Some(String::from("New body")), status: None, publish_at: None };
+        let rewritten = repository.update(id, &rewrite, None, Some(edited.post.version), None).unwrap();
+        assert_eq!(rewritten.post.body, "New body");
+    }
+}
@@ -0,0 +1,270 @@
+//! `PostRepository` kept in memory, for running and testing the posts API without a database.
+//! Nothing survives a restart, there are no users to credit posts to, nobody comments, and
+//! edits keep no revisions.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use chrono::Utc;
+
+use crate::models::{NewPost, Post, PostChanges, PostDetails, PostStatus};
+use crate::slugs;
+use super::{Cursor, PostFilter, PostRepository, PostSort, RepositoryError, Slice, SlugMatch};
+
+struct StoredPost {
+    post: Post,
+    tags: Vec<String>,
+    trashed: bool,
+}
+
+impl StoredPost {
+    fn details(&self) -> PostDetails {
+        PostDetails {
+            post: self.post.clone(),
+            author: None,
+            tags: self.tags.clone(),
+            comment_count: 0,
+            body_html: None,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    posts: BTreeMap<i32, StoredPost>,
+    /// Slugs posts were renamed away from, and the post each belongs to.
+    former_slugs: HashMap<String, i32>,
+    last_id: i32,
+}
+
+impl Store {
+    fn live(&self, id: i32) -> Result<&StoredPost, RepositoryError> {
+        self.posts.get(&id).filter(|stored| !stored.trashed).ok_or(RepositoryError::NotFound)
+    }
+
+    /// A slug for `title` that no other post uses or used to use, as `slugs::unique_slug`.
+    fn unique_slug(&self, title: &str, post_id: Option<i32>) -> String {
+        slugs::first_free(&slugs::slugify(title), |slug| {
+            self.posts.values().any(|stored| stored.post.slug == slug && Some(stored.post.id) != post_id)
+                || self.former_slugs.get(slug).map_or(false, |&id| Some(id) != post_id)
+        })
+    }
+}
+
+/// Orders posts the way `sort` lists them, with ids breaking ties.
+fn listing_order(sort: PostSort, a: &Post, b: &Post) -> Ordering {
+    let ascending = match sort {
+        PostSort::Id | PostSort::IdDesc => a.id.cmp(&b.id),
+        PostSort::Title => (&a.title, a.id).cmp(&(&b.title, b.id)),
+        PostSort::CreatedAt | PostSort::CreatedAtDesc => (a.created_at, a.id).cmp(&(b.created_at, b.id)),
+        PostSort::UpdatedAt | PostSort::UpdatedAtDesc => (a.updated_at, a.id).cmp(&(b.updated_at, b.id)),
+    };
+    if sort.is_descending() {
+        ascending.reverse()
+    } else {
+        ascending
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryPostRepository {
+    store: RwLock<Store>,
+}
+
+impl MemoryPostRepository {
+    pub fn new() -> MemoryPostRepository {
+        MemoryPostRepository::default()
+    }
+
+    // A handler that panicked can't have left a post half written, as every change below is
+    // checked before anything is touched, so a poisoned lock is still safe to use.
+    fn read(&self) -> RwLockReadGuard<Store> {
+        self.store.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<Store> {
+        self.store.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl PostRepository for MemoryPostRepository {
+    fn list(&self, filter: &PostFilter, sort: PostSort, cursor: Option<Cursor>, limit: i64) -> Result<Slice, RepositoryError> {
+        let store = self.read();
+        let mut posts = store.posts.values()
+            .filter(|stored| !stored.trashed && filter.matches(&stored.post, &stored.tags))
+            .collect::<Vec<_>>();
+        posts.sort_by(|a, b| listing_order(sort, &a.post, &b.post));
+
+        let forwards = cursor.map_or(true, Cursor::is_forwards);
+        if let Some(cursor) = cursor {
+            let key = &store.live(cursor.id())
+                .map_err(|_| RepositoryError::InvalidCursor(cursor.id()))?
+                .post;
+            let beyond = if forwards { Ordering::Greater } else { Ordering::Less };
+            posts.retain(|stored| listing_order(sort, &stored.post, key) == beyond);
+        }
+
+        // Walking backwards, the page is the posts nearest before the cursor.
+        if !forwards {
+            posts.reverse();
+        }
+        let has_more = posts.len() as i64 > limit;
+        posts.truncate(limit as usize);
+        if !forwards {
+            posts.reverse();
+        }
+        Ok(Slice { items: posts.into_iter().map(StoredPost::details).collect(), has_more })
+    }
+
+    fn find(&self, id: i32) -> Result<PostDetails, RepositoryError> {
+        self.read().live(id).map(StoredPost::details)
+    }
+
+    fn find_by_slug(&self, slug: &str) -> Result<SlugMatch, RepositoryError> {
+        let store = self.read();
+        if let Some(stored) = store.posts.values().find(|stored| !stored.trashed && stored.post.slug == slug) {
+            return Ok(SlugMatch::Current(stored.details()));
+        }
+
+        let id = *store.former_slugs.get(slug).ok_or(RepositoryError::NotFound)?;
+        store.live(id).map(|stored| SlugMatch::Former(stored.post.slug.clone()))
+    }
+
+    // There are no users to look `author` up in, so posts made here have none.
+    fn create(&self, post: NewPost, tags: &[String], _author: Option<&str>) -> Result<PostDetails, RepositoryError> {
+        let mut store = self.write();
+        store.last_id += 1;
+        let now = Utc::now();
+        let post = Post {
+            id: store.last_id,
+            slug: store.unique_slug(&post.title, None),
+            title: post.title,
+            body: post.body,
+            status: PostStatus::Draft,
+            publish_at: None,
+            created_at: now,
+            updated_at: now,
+            author_id: None,
+            version: 1,
+        };
+
+        let stored = StoredPost { post, tags: tags.to_vec(), trashed: false };
+        let details = stored.details();
+        store.posts.insert(stored.post.id, stored);
+        Ok(details)
+    }
+
+    fn update(&self, id: i32, changes: &PostChanges, tags: Option<&[String]>, version: Option<i32>,
+              _editor: Option<&str>) -> Result<PostDetails, RepositoryError> {
+        let mut store = self.write();
+        let store = &mut *store;
+        let current = store.live(id)?;
+        if version.map_or(false, |version| version != current.post.version) {
+            return Err(RepositoryError::Outdated(Box::new(current.details())));
+        }
+        if let Some(to) = changes.status {
+            if !current.post.status.can_become(to) {
+                return Err(RepositoryError::Transition { id, from: current.post.status, to });
+            }
+        }
+        let slug = match changes.title {
+            Some(ref title) if *title != current.post.title => Some(store.unique_slug(title, Some(id))),
+            _ => None,
+        };
+        let retagged = tags.map_or(false, |tags| current.tags != tags);
+
+        let stored = store.posts.get_mut(&id).expect("post was looked up above");
+        if let Some(slug) = slug {
+            // The new slug may be one this post had before, in which case it stops being a redirect.
+            store.former_slugs.remove(&slug);
+            store.former_slugs.insert(std::mem::replace(&mut stored.post.slug, slug), id);
+        }
+        let post = &mut stored.post;
+        if let Some(ref title) = changes.title {
+            post.title = title.clone();
+        }
+        if let Some(ref body) = changes.body {
+            post.body = body.clone();
+        }
+        if let Some(status) = changes.status {
+            post.status = status;
+        }
+        if let Some(publish_at) = changes.publish_at {
+            post.publish_at = publish_at;
+        }
+        if retagged {
+            stored.tags = tags.unwrap_or_default().to_vec();
+        }
+        if !changes.is_empty() || retagged {
+            stored.post.version += 1;
+            stored.post.updated_at = Utc::now();
+        }
+        Ok(stored.details())
+    }
+
+    fn trash(&self, id: i32, version: Option<i32>) -> Result<(), RepositoryError> {
+        let mut store = self.write();
+        let current = store.live(id)?;
+        if version.map_or(false, |version| version != current.post.version) {
+            return Err(RepositoryError::Outdated(Box::new(current.details())));
+        }
+
+        let stored = store.posts.get_mut(&id).expect("post was looked up above");
+        stored.trashed = true;
+        stored.post.version += 1;
+        stored.post.updated_at = Utc::now();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_post(title: &str) -> NewPost {
+        NewPost { title: title.to_string(), body: String::from("Body"), slug: String::new(), author_id: None }
+    }
+
+    fn retitle(title: &str) -> PostChanges {
+        PostChanges { title: Some(title.to_string()), body: None, status: None, publish_at: None }
+    }
+
+    #[test]
+    fn edits_need_the_current_version() {
+        let repository = MemoryPostRepository::new();
+        let post = repository.create(new_post("Hello"), &[], None).unwrap();
+        let id = post.post.id;
+
+        let edited = repository.update(id, &retitle("Hello again"), None, Some(1), None).unwrap();
+        assert_eq!(edited.post.version, 2);
+        assert_eq!(edited.post.slug, "hello-again");
+        match repository.update(id, &retitle("Lost"), None, Some(1), None) {
+            Err(RepositoryError::Outdated(current)) => assert_eq!(current.post.title, "Hello again"),
+            _ => panic!("an outdated edit was applied"),
+        }
+        match repository.find_by_slug("hello") {
+            Ok(SlugMatch::Former(slug)) => assert_eq!(slug, "hello-again"),
+            _ => panic!("the old slug doesn't redirect"),
+        }
+    }
+
+    #[test]
+    fn listings_page_both_ways() {
+        let repository = MemoryPostRepository::new();
+        for title in &["A", "B", "C", "D"] {
+            repository.create(new_post(title), &[], None).unwrap();
+        }
+        let ids = |slice: Slice| slice.items.iter().map(|item| item.post.id).collect::<Vec<_>>();
+
+        let filter = PostFilter::default();
+        let first = repository.list(&filter, PostSort::IdDesc, None, 2).unwrap();
+        assert!(first.has_more);
+        assert_eq!(ids(first), vec![4, 3]);
+        let second = repository.list(&filter, PostSort::IdDesc, Some(Cursor::After(3)), 2).unwrap();
+        assert!(!second.has_more);
+        assert_eq!(ids(second), vec![2, 1]);
+        let back = repository.list(&filter, PostSort::IdDesc, Some(Cursor::Before(2)), 2).unwrap();
+        assert_eq!(ids(back), vec![4, 3]);
+    }
+}
@@ -0,0 +1,173 @@
+//! Storage for posts behind the `PostRepository` trait, so that the posts API can run against
+//! the database or, for tests and trying things out, entirely in memory.
+
+use chrono::{DateTime, Utc};
+use diesel::result::Error;
+use rocket::http::RawStr;
+use rocket::request::FromFormValue;
+
+use crate::models::{NewPost, Post, PostChanges, PostDetails, PostStatus};
+
+pub mod database;
+pub mod memory;
+
+pub use self::database::DatabasePostRepository;
+pub use self::memory::MemoryPostRepository;
+
+/// Sort order of the posts listing: `sort=id`, `sort=title`, `sort=created_at` or
+/// `sort=updated_at`. A leading `-` sorts ids and timestamps newest first.
+#[derive(Clone, Copy, PartialEq)]
+pub enum PostSort {
+    Id,
+    IdDesc,
+    Title,
+    CreatedAt,
+    CreatedAtDesc,
+    UpdatedAt,
+    UpdatedAtDesc,
+}
+
+impl PostSort {
+    pub fn is_descending(self) -> bool {
+        match self {
+            PostSort::IdDesc | PostSort::CreatedAtDesc | PostSort::UpdatedAtDesc => true,
+            PostSort::Id | PostSort::Title | PostSort::CreatedAt | PostSort::UpdatedAt => false,
+        }
+    }
+}
+
+impl<'v> FromFormValue<'v> for PostSort {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<PostSort, &'v RawStr> {
+        match form_value.as_str() {
+            "id" => Ok(PostSort::Id),
+            "-id" => Ok(PostSort::IdDesc),
+            "title" => Ok(PostSort::Title),
+            "created_at" => Ok(PostSort::CreatedAt),
+            "-created_at" => Ok(PostSort::CreatedAtDesc),
+            "updated_at" => Ok(PostSort::UpdatedAt),
+            "-updated_at" => Ok(PostSort::UpdatedAtDesc),
+            _ => Err(form_value),
+        }
+    }
+}
+
+/// Which posts a listing is narrowed down to. Every filter that is set has to match.
+#[derive(Default)]
+pub struct PostFilter {
+    pub status: Option<PostStatus>,
+    /// Matched case insensitively anywhere in the title.
+    pub title_contains: Option<String>,
+    /// A normalized tag name.
+    pub tag: Option<String>,
+    pub author: Option<i32>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+impl PostFilter {
+    /// Whether `post`, filed under `tags`, passes the filter. Backends that can't query for it
+    /// check the posts one by one with this.
+    pub fn matches(&self, post: &Post, tags: &[String]) -> bool {
+        self.status.map_or(true, |status| post.status == status)
+            && self.title_contains.as_ref().map_or(true, |fragment| {
+                post.title.to_lowercase().contains(&fragment.to_lowercase())
+            })
+            && self.tag.as_ref().map_or(true, |tag| tags.contains(tag))
+            && self.author.map_or(true, |author| post.author_id == Some(author))
+            && self.created_after.map_or(true, |created_after| post.created_at > created_after)
+            && self.created_before.map_or(true, |created_before| post.created_at < created_before)
+            && self.updated_after.map_or(true, |updated_after| post.updated_at > updated_after)
+            && self.updated_before.map_or(true, |updated_before| post.updated_at < updated_before)
+    }
+}
+
+/// Where a page of a listing starts: just past the post with the given id, in either direction.
+#[derive(Clone, Copy)]
+pub enum Cursor {
+    After(i32),
+    Before(i32),
+}
+
+impl Cursor {
+    pub fn id(self) -> i32 {
+        match self {
+            Cursor::After(id) | Cursor::Before(id) => id,
+        }
+    }
+
+    pub fn is_forwards(self) -> bool {
+        match self {
+            Cursor::After(_) => true,
+            Cursor::Before(_) => false,
+        }
+    }
+}
+
+/// A page of a listing, in listing order whichever way it was walked, and whether there are
+/// more posts beyond it in the direction it was walked.
+pub struct Slice {
+    pub items: Vec<PostDetails>,
+    pub has_more: bool,
+}
+
+/// A post looked up by slug: either its current slug, or one it had before and has since
+/// been renamed away from.
+pub enum SlugMatch {
+    Current(PostDetails),
+    Former(String),
+}
+
+/// Why a repository couldn't do what it was asked.
+#[derive(Debug)]
+pub enum RepositoryError {
+    /// There is no such post, or it is in the trash.
+    NotFound,
+    /// The edit was based on another version of the post, which is handed back as it is now.
+    Outdated(Box<PostDetails>),
+    /// Post `id` can't move into status `to` from the one it is in.
+    Transition { id: i32, from: PostStatus, to: PostStatus },
+    /// A listing cursor that doesn't name a post the listing could page from.
+    InvalidCursor(i32),
+    /// The storage can't be reached right now.
+    Unavailable,
+    Database(Error),
+}
+
+impl From<Error> for RepositoryError {
+    fn from(error: Error) -> RepositoryError {
+        match error {
+            Error::NotFound => RepositoryError::NotFound,
+            error => RepositoryError::Database(error),
+        }
+    }
+}
+
+/// Where posts are kept. Trashed posts are out of sight of every method here; see
+/// `crate::trash` for those. Edits given a `version` only apply to that version of the post.
+pub trait PostRepository: Send + Sync {
+    /// Up to `limit` posts passing `filter` in `sort` order, starting past `cursor`.
+    fn list(&self, filter: &PostFilter, sort: PostSort, cursor: Option<Cursor>, limit: i64) -> Result<Slice, RepositoryError>;
+
+    fn find(&self, id: i32) -> Result<PostDetails, RepositoryError>;
+
+    fn find_by_slug(&self, slug: &str) -> Result<SlugMatch, RepositoryError>;
+
+    /// Saves a new draft filed under `tags`, which must be normalized, with a slug made from
+    /// its title. `author` is the username of whoever wrote it.
+    fn create(&self, post: NewPost, tags: &[String], author: Option<&str>) -> Result<PostDetails, RepositoryError>;
+
+    /// Applies `changes`, and refiles the post under `tags` if given. A new title gets the post
+    /// a new slug, and `editor` is who to credit the edit to where revisions are kept.
+    fn update(&self, id: i32, changes: &PostChanges, tags: Option<&[String]>, version: Option<i32>,
+              editor: Option<&str>) -> Result<PostDetails, RepositoryError>;
+
+    /// Moves the post to the trash.
+    fn trash(&self, id: i32, version: Option<i32>) -> Result<(), RepositoryError>;
+}
+
+/// The repository the API is using, as kept in managed state.
+pub type Repository = Box<dyn PostRepository>;
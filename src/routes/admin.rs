@@ -0,0 +1,20 @@
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::connection_pool::DbConn;
+use sk_rust_web::migrations::{self, MigrationStatus};
+
+use super::{db_error, Editor};
+
+// Which of the migrations built into the app the database has had, and which it hasn't. They
+// all run before the app launches, so pending ones only show up if one was reverted since.
+#[get("/admin/migrations")]
+pub fn migration_status(editor: Editor, connection: DbConn) -> Result<Json<MigrationStatus>, Status> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized);
+    }
+
+    migrations::status(&*connection)
+        .map(Json)
+        .map_err(db_error)
+}
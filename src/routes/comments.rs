@@ -0,0 +1,102 @@
+use diesel::prelude::*;
+use rocket::http::{RawStr, Status};
+use rocket::response::status;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::connection_pool::{DbConn, DbConnection, ReadDbConn};
+use sk_rust_web::dialect;
+use sk_rust_web::models::{live_posts, Comment, CommentModeration, CommentNode, CommentStatus, NewComment};
+use sk_rust_web::schema::{comments, posts};
+use sk_rust_web::threads;
+
+use super::{db_error, ApiError, Editor};
+
+/// Answers 404 unless post `id` exists and isn't in the trash.
+fn find_post(id: i32, connection: &DbConnection) -> Result<(), Status> {
+    live_posts().filter(posts::id.eq(id))
+        .select(posts::id)
+        .first::<i32>(connection)
+        .map(|_| ())
+        .map_err(db_error)
+}
+
+// Readers only ever see approved comments; the rest wait in the moderation queue.
+#[get("/posts/<id>/comments")]
+pub fn post_comments(id: i32, connection: ReadDbConn) -> Result<Json<Vec<CommentNode>>, Status> {
+    find_post(id, &*connection)?;
+
+    let comments = comments::table
+        .filter(comments::post_id.eq(id))
+        .filter(comments::status.eq(CommentStatus::Approved))
+        .order(comments::id.asc())
+        .load::<Comment>(&*connection)
+        .map_err(db_error)?;
+    Ok(Json(threads::thread(comments)))
+}
+
+// New comments start out pending, so the one handed back isn't visible to readers yet.
+#[post("/posts/<id>/comments", format = "json", data = "<comment>")]
+pub fn create_comment(id: i32, comment: Json<NewComment>, connection: DbConn) -> Result<status::Created<Json<Comment>>, ApiError> {
+    let mut comment = comment.into_inner();
+    comment.post_id = id;
+    if comment.author.trim().is_empty() || comment.body.trim().is_empty() {
+        return Err(ApiError::BadRequest(String::from("A comment needs an author and a body.")));
+    }
+
+    find_post(id, &*connection)?;
+    if let Some(parent_id) = comment.parent_id {
+        let parent = comments::table
+            .filter(comments::id.eq(parent_id))
+            .filter(comments::post_id.eq(id))
+            .select(comments::id)
+            .first::<i32>(&*connection)
+            .optional()
+            .map_err(db_error)?;
+        if parent.is_none() {
+            return Err(ApiError::BadRequest(format!("Post {} has no comment {} to reply to.", id, parent_id)));
+        }
+    }
+
+    let comment = connection.transaction(|| {
+        diesel::insert_into(comments::table).values(&comment).execute(&*connection)?;
+        comments::table.find(dialect::last_insert_id(&*connection)?).first::<Comment>(&*connection)
+    }).map_err(db_error)?;
+    let location = uri!(post_comments: id).to_string();
+    Ok(status::Created(location, Some(Json(comment))))
+}
+
+// The moderation queue across all posts, oldest first. Pending comments unless asked otherwise.
+#[get("/comments?<status>")]
+pub fn moderation_queue(status: Option<Result<CommentStatus, &RawStr>>, editor: Editor,
+                        connection: DbConn) -> Result<Json<Vec<Comment>>, ApiError> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized.into());
+    }
+    let status = match status {
+        Some(Ok(status)) => status,
+        Some(Err(value)) => return Err(ApiError::BadRequest(
+            format!("Invalid value '{}' for query parameter 'status'.", value))),
+        None => CommentStatus::Pending,
+    };
+
+    comments::table
+        .filter(comments::status.eq(status))
+        .order(comments::id.asc())
+        .load::<Comment>(&*connection)
+        .map(Json)
+        .map_err(|error| db_error(error).into())
+}
+
+#[patch("/comments/<id>", format = "json", data = "<moderation>")]
+pub fn moderate_comment(id: i32, moderation: Json<CommentModeration>, editor: Editor, connection: DbConn) -> Result<Json<Comment>, Status> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized);
+    }
+
+    diesel::update(comments::table.filter(comments::id.eq(id)))
+        .set(comments::status.eq(moderation.status))
+        .execute(&*connection)
+        .and_then(|_| comments::table.find(id).first::<Comment>(&*connection))
+        .map(Json)
+        .map_err(db_error)
+}
@@ -0,0 +1,106 @@
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::Content;
+use rocket::State;
+
+use sk_rust_web::authors;
+use sk_rust_web::connection_pool::{DbConnection, ReadDbConn};
+use sk_rust_web::feeds::{self, Channel, Entry, FEED_SIZE};
+use sk_rust_web::markdown::RenderCache;
+use sk_rust_web::models::{live_posts, Post, PostStatus, POST_COLUMNS};
+use sk_rust_web::schema::{post_tags, posts, tags};
+use sk_rust_web::site::Site;
+use sk_rust_web::sql_types::Timestamptz;
+
+use super::db_error;
+use super::posts::HtmlPage;
+use super::tags::find_tag;
+
+/// The newest published posts, filed under `tag` if given. Posts published straight away have
+/// no `publish_at`, so they count as published when they were created.
+fn published_posts(tag: Option<&str>, connection: &DbConnection) -> Result<Vec<Post>, Status> {
+    let mut query = live_posts()
+        .filter(posts::status.eq(PostStatus::Published))
+        .select(POST_COLUMNS)
+        .into_boxed();
+    if let Some(tag) = tag {
+        query = query.filter(posts::id.eq_any(post_tags::table
+            .inner_join(tags::table)
+            .filter(tags::name.eq(tag))
+            .select(post_tags::post_id)));
+    }
+
+    query
+        .order(sql::<Timestamptz>("coalesce(posts.publish_at, posts.created_at)").desc())
+        .then_order_by(posts::id.desc())
+        .limit(FEED_SIZE)
+        .load::<Post>(connection)
+        .map_err(db_error)
+}
+
+fn entries(posts: Vec<Post>, site: &Site, cache: &RenderCache, connection: &DbConnection) -> Result<Vec<Entry>, Status> {
+    let authors = authors::for_posts(connection, &posts).map_err(db_error)?;
+    Ok(posts.into_iter().zip(authors).map(|(post, author)| Entry {
+        url: site.url(&uri!(crate::routes::posts::post_page: HtmlPage(post.id)).to_string()),
+        author: author.map(|author| author.display_name),
+        html: cache.body_html(&post),
+        published: post.publish_at.unwrap_or(post.created_at),
+        updated: post.updated_at,
+        title: post.title,
+    }).collect())
+}
+
+fn rss_content(xml: String) -> Content<String> {
+    Content(ContentType::new("application", "rss+xml"), xml)
+}
+
+fn atom_content(xml: String) -> Content<String> {
+    Content(ContentType::new("application", "atom+xml"), xml)
+}
+
+fn site_channel(site: &Site, self_link: String) -> Channel {
+    Channel {
+        title: site.title.clone(),
+        link: site.url(&uri!(crate::routes::posts::all_posts: _, _, _, _).to_string()),
+        self_link: site.url(&self_link),
+    }
+}
+
+fn tag_channel(site: &Site, tag: &str, self_link: String) -> Channel {
+    Channel {
+        title: format!("{}: {}", site.title, tag),
+        link: site.url(&uri!(crate::routes::tags::tag_posts: tag, _, _, _, _).to_string()),
+        self_link: site.url(&self_link),
+    }
+}
+
+#[get("/feed.rss")]
+pub fn rss_feed(site: State<Site>, cache: State<RenderCache>, connection: ReadDbConn) -> Result<Content<String>, Status> {
+    let entries = entries(published_posts(None, &*connection)?, &site, &cache, &*connection)?;
+    let channel = site_channel(&site, uri!(rss_feed).to_string());
+    Ok(rss_content(feeds::rss(&channel, &entries)))
+}
+
+#[get("/feed.atom")]
+pub fn atom_feed(site: State<Site>, cache: State<RenderCache>, connection: ReadDbConn) -> Result<Content<String>, Status> {
+    let entries = entries(published_posts(None, &*connection)?, &site, &cache, &*connection)?;
+    let channel = site_channel(&site, uri!(atom_feed).to_string());
+    Ok(atom_content(feeds::atom(&channel, &site, &entries)))
+}
+
+#[get("/tags/<tag>/feed.rss")]
+pub fn tag_rss_feed(tag: String, site: State<Site>, cache: State<RenderCache>, connection: ReadDbConn) -> Result<Content<String>, Status> {
+    let tag = find_tag(&tag, &*connection)?;
+    let entries = entries(published_posts(Some(&tag), &*connection)?, &site, &cache, &*connection)?;
+    let channel = tag_channel(&site, &tag, uri!(tag_rss_feed: &tag).to_string());
+    Ok(rss_content(feeds::rss(&channel, &entries)))
+}
+
+#[get("/tags/<tag>/feed.atom")]
+pub fn tag_atom_feed(tag: String, site: State<Site>, cache: State<RenderCache>, connection: ReadDbConn) -> Result<Content<String>, Status> {
+    let tag = find_tag(&tag, &*connection)?;
+    let entries = entries(published_posts(Some(&tag), &*connection)?, &site, &cache, &*connection)?;
+    let channel = tag_channel(&site, &tag, uri!(tag_atom_feed: &tag).to_string());
+    Ok(atom_content(feeds::atom(&channel, &site, &entries)))
+}
@@ -0,0 +1,151 @@
+use diesel::prelude::*;
+use diesel::result::{DatabaseErrorKind, Error};
+use rocket::http::Status;
+use rocket::Outcome;
+use rocket::request::{self, FormParseError, FromRequest, Request};
+use rocket::response::{self, status, Responder};
+use rocket_contrib::json::Json;
+
+use sk_rust_web::authors;
+use sk_rust_web::connection_pool::DbConnection;
+use sk_rust_web::models::PostDetails;
+use sk_rust_web::repository::RepositoryError;
+
+pub mod admin;
+pub mod comments;
+pub mod feeds;
+pub mod posts;
+pub mod revisions;
+pub mod sitemap;
+pub mod tags;
+pub mod transfer;
+pub mod trash;
+pub mod users;
+
+/// Maps a Diesel error onto the status a handler should answer with: a missing row is a 404, a
+/// clash with a unique constraint is a 409, anything else is logged and reported as a 500.
+pub fn db_error(error: Error) -> Status {
+    match error {
+        Error::NotFound => Status::NotFound,
+        Error::DatabaseError(DatabaseErrorKind::UniqueViolation, _) => Status::Conflict,
+        error => {
+            println!("Database error: {}", error);
+            Status::InternalServerError
+        }
+    }
+}
+
+/// Failure of an API handler. Client errors are answered with a message explaining what was
+/// wrong, everything else is left to the catcher for its status.
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Conflict(String),
+    /// A write that didn't say which version of the post it was based on.
+    PreconditionRequired(String),
+    /// A write based on an outdated version, answered with the post as it is now.
+    Outdated(Status, Box<PostDetails>),
+    Status(Status),
+}
+
+impl From<Status> for ApiError {
+    fn from(status: Status) -> ApiError {
+        ApiError::Status(status)
+    }
+}
+
+// An outdated edit is answered with a 409 here; edits that sent `If-Match` want a 412 instead,
+// which they pick before converting.
+impl From<RepositoryError> for ApiError {
+    fn from(error: RepositoryError) -> ApiError {
+        match error {
+            RepositoryError::NotFound => ApiError::Status(Status::NotFound),
+            RepositoryError::Outdated(current) => ApiError::Outdated(Status::Conflict, current),
+            RepositoryError::Transition { id, from, to } => ApiError::Conflict(
+                format!("Post {} can't move from '{}' to '{}'.", id, from.as_str(), to.as_str())),
+            RepositoryError::InvalidCursor(id) => ApiError::BadRequest(format!("Invalid cursor '{}'.", id)),
+            RepositoryError::Unavailable => ApiError::Status(Status::ServiceUnavailable),
+            RepositoryError::Database(error) => ApiError::Status(db_error(error)),
+        }
+    }
+}
+
+impl<'f> From<FormParseError<'f>> for ApiError {
+    fn from(error: FormParseError<'f>) -> ApiError {
+        ApiError::BadRequest(match error {
+            FormParseError::BadValue(name, value) =>
+                format!("Invalid value '{}' for query parameter '{}'.", value, name),
+            FormParseError::Unknown(name, _) => format!("Unknown query parameter '{}'.", name),
+            FormParseError::Missing(name) => format!("Missing query parameter '{}'.", name),
+        })
+    }
+}
+
+impl<'r> Responder<'r> for ApiError {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        match self {
+            ApiError::BadRequest(message) => status::BadRequest(Some(message)).respond_to(request),
+            ApiError::Conflict(message) => status::Conflict(Some(message)).respond_to(request),
+            ApiError::PreconditionRequired(message) =>
+                status::Custom(Status::PreconditionRequired, message).respond_to(request),
+            ApiError::Outdated(status, current) => status::Custom(status, Json(*current)).respond_to(request),
+            ApiError::Status(status) => Err(status),
+        }
+    }
+}
+
+/// Whoever is making a change, taken from the private `user_id` cookie. Requests without one
+/// are let through anonymously.
+pub struct Editor(pub Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Editor {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Editor, ()> {
+        let editor = request.cookies()
+            .get_private("user_id")
+            .map(|cookie| cookie.value().to_string());
+        Outcome::Success(Editor(editor))
+    }
+}
+
+impl Editor {
+    /// The name the editor signed in as, if anybody is signed in.
+    pub fn username(&self) -> Option<&str> {
+        self.0.as_ref().map(String::as_str)
+    }
+
+    /// The signed in user's id, or `None` when nobody is signed in or the name isn't a user.
+    pub fn user_id(&self, connection: &DbConnection) -> QueryResult<Option<i32>> {
+        match self.0 {
+            Some(ref username) => authors::find_id(connection, username),
+            None => Ok(None),
+        }
+    }
+}
+
+/// The `If-Match` header of a write. ETags of posts start with the post's version, so that is
+/// all that is taken from them.
+pub enum IfMatch {
+    Missing,
+    Any,
+    Version(i32),
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<IfMatch, ()> {
+        let if_match = match request.headers().get_one("If-Match").map(str::trim) {
+            None => IfMatch::Missing,
+            Some("*") => IfMatch::Any,
+            // `If-Match` uses the strong comparison, so weak or foreign tags never match, which
+            // version 0 makes sure of as no post has it.
+            Some(etag) => IfMatch::Version(etag.strip_prefix('"')
+                .and_then(|etag| etag.split(|c| c == '-' || c == '"').next())
+                .and_then(|version| version.parse().ok())
+                .unwrap_or(0)),
+        };
+        Outcome::Success(if_match)
+    }
+}
@@ -0,0 +1,365 @@
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use rocket::http::Status;
+use rocket::http::uri::{Formatter, Origin, Path, UriDisplay};
+use rocket::http::RawStr;
+use rocket::request::{Form, FormItems, FormParseError, FromForm, FromFormValue, FromParam};
+use rocket::response::{content, status, Redirect};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::models::{NewPost, PostChanges, PostDetails, PostReplacement, PostStatus, PostInput, SearchResult};
+use sk_rust_web::caching::Conditional;
+use sk_rust_web::connection_pool::ReadDbConn;
+use sk_rust_web::markdown::{self, RenderCache};
+use sk_rust_web::negotiation::{Format, Negotiated};
+use sk_rust_web::repository::{Cursor, PostFilter, PostRepository, PostSort, Repository, RepositoryError, SlugMatch};
+use sk_rust_web::tagging;
+use sk_rust_web::pagination::{self, Page};
+
+use super::{db_error, ApiError, Editor, IfMatch};
+
+/// Filters accepted by the posts listing. `FromForm` is implemented by hand rather than derived:
+/// the derive turns a malformed `Option` field into `None`, and we want to answer those with a
+/// 400 naming the parameter instead of silently ignoring the filter.
+#[derive(Default)]
+pub struct PostFilters {
+    pub filter: PostFilter,
+    pub sort: Option<PostSort>,
+    /// `render=html`: also hand out each body rendered from Markdown, as `body_html`.
+    pub render_html: bool,
+}
+
+impl<'f> FromForm<'f> for PostFilters {
+    type Error = FormParseError<'f>;
+
+    fn from_form(items: &mut FormItems<'f>, strict: bool) -> Result<PostFilters, FormParseError<'f>> {
+        let mut filters = PostFilters::default();
+        let filter = &mut filters.filter;
+        for item in items {
+            let (key, value) = (item.key, item.value);
+            let bad_value = FormParseError::BadValue(key, value);
+            match key.as_str() {
+                "status" => filter.status = Some(PostStatus::from_form_value(value).map_err(|_| bad_value)?),
+                "title_contains" => filter.title_contains = Some(value.url_decode().map_err(|_| bad_value)?),
+                "tag" => filter.tag = Some(value.url_decode().ok()
+                    .and_then(|tag| tagging::normalize(&tag))
+                    .ok_or(bad_value)?),
+                "author" => filter.author = Some(i32::from_form_value(value).map_err(|_| bad_value)?),
+                "created_after" => filter.created_after = Some(timestamp(value).ok_or(bad_value)?),
+                "created_before" => filter.created_before = Some(timestamp(value).ok_or(bad_value)?),
+                "updated_after" => filter.updated_after = Some(timestamp(value).ok_or(bad_value)?),
+                "updated_before" => filter.updated_before = Some(timestamp(value).ok_or(bad_value)?),
+                "render" => filters.render_html = render_html(value).ok_or(bad_value)?,
+                "sort" => filters.sort = Some(PostSort::from_form_value(value).map_err(|_| bad_value)?),
+                _ if strict => return Err(FormParseError::Unknown(key, value)),
+                _ => {}
+            }
+        }
+        Ok(filters)
+    }
+}
+
+/// Parses the value of `render`, of which `html` is the only one so far.
+fn render_html(value: &RawStr) -> Option<bool> {
+    if value == "html" {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// Parses an RFC 3339 timestamp such as `2020-05-02T19:42:27Z`. A `+` in the offset has to be
+/// sent as `%2B`, or it decodes to a space.
+fn timestamp(value: &RawStr) -> Option<DateTime<Utc>> {
+    let value = value.url_decode().ok()?;
+    DateTime::parse_from_rfc3339(&value).ok().map(|timestamp| timestamp.with_timezone(&Utc))
+}
+
+// JSON unless the `Accept` header asks for CSV, MessagePack or HTML instead.
+#[get("/posts?<limit>&<after>&<before>&<filters..>")]
+pub fn all_posts(limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                 filters: Result<Form<PostFilters>, FormParseError>, origin: &Origin,
+                 cache: State<RenderCache>, repository: State<Repository>) -> Result<Conditional<Negotiated<PostDetails>>, ApiError> {
+    list_posts(filters?.into_inner(), limit, after, before, origin, &cache, &**repository)
+        .map(|page| conditional_page(page, None))
+}
+
+// For clients that can't set `Accept`, such as a spreadsheet importing from a URL.
+#[get("/posts.csv?<limit>&<after>&<before>&<filters..>")]
+pub fn all_posts_csv(limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                     filters: Result<Form<PostFilters>, FormParseError>, origin: &Origin,
+                     cache: State<RenderCache>, repository: State<Repository>) -> Result<Conditional<Negotiated<PostDetails>>, ApiError> {
+    list_posts(filters?.into_inner(), limit, after, before, origin, &cache, &**repository)
+        .map(|page| conditional_page(page, Some(Format::Csv)))
+}
+
+#[get("/posts.msgpack?<limit>&<after>&<before>&<filters..>")]
+pub fn all_posts_msgpack(limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                         filters: Result<Form<PostFilters>, FormParseError>, origin: &Origin,
+                         cache: State<RenderCache>, repository: State<Repository>) -> Result<Conditional<Negotiated<PostDetails>>, ApiError> {
+    list_posts(filters?.into_inner(), limit, after, before, origin, &cache, &**repository)
+        .map(|page| conditional_page(page, Some(Format::MessagePack)))
+}
+
+/// Lets clients revalidate a page of posts in `format`, or in whichever one `Accept` asks for.
+/// Pages only get an ETag: no date says when posts left a page or moved along it.
+pub fn conditional_page(page: Page<PostDetails>, format: Option<Format>) -> Conditional<Negotiated<PostDetails>> {
+    Conditional { last_modified: None, version: None, response: Negotiated { format, page } }
+}
+
+/// Keyset pagination over the chosen sort order: `after` walks forwards, `before` walks
+/// backwards, and the neighbouring pages are linked from the `Link` header. Sorting by title or
+/// by a timestamp uses `(column, id)` as the key so that ties still page deterministically.
+pub fn list_posts(filters: PostFilters, limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                  origin: &Origin, cache: &RenderCache, repository: &dyn PostRepository) -> Result<Page<PostDetails>, ApiError> {
+    let sort = filters.sort.unwrap_or(PostSort::Id);
+    let limit = pagination::page_size(limit);
+    let cursor = match (after, before) {
+        (Some(after), _) => Some(Cursor::After(after)),
+        (None, Some(before)) => Some(Cursor::Before(before)),
+        (None, None) => None,
+    };
+    let forwards = cursor.map_or(true, Cursor::is_forwards);
+    let param = if forwards { "after" } else { "before" };
+
+    let slice = repository.list(&filters.filter, sort, cursor, limit).map_err(|error| match error {
+        RepositoryError::InvalidCursor(id) => ApiError::BadRequest(
+            format!("Invalid value '{}' for query parameter '{}'.", id, param)),
+        error => ApiError::from(error),
+    })?;
+    let html = if filters.render_html { Some(cache) } else { None };
+    let items = slice.items.into_iter().map(|item| rendered(item, html)).collect::<Vec<_>>();
+
+    let (first, last) = match (items.first(), items.last()) {
+        (Some(first), Some(last)) => (first.post.id, last.post.id),
+        _ => return Ok(Page { items, next: None, prev: None }),
+    };
+    let has_next = if forwards { slice.has_more } else { true };
+    let has_prev = if forwards { cursor.is_some() } else { slice.has_more };
+
+    Ok(Page {
+        next: if has_next { Some(pagination::cursor_uri(origin, limit, "after", last)) } else { None },
+        prev: if has_prev { Some(pagination::cursor_uri(origin, limit, "before", first)) } else { None },
+        items,
+    })
+}
+
+/// Adds the body rendered to HTML when given a cache to render through.
+fn rendered(mut details: PostDetails, html: Option<&RenderCache>) -> PostDetails {
+    details.body_html = html.map(|cache| cache.body_html(&details.post));
+    details
+}
+
+/// Looks up the `render` query parameter of a single post route.
+fn html_cache<'c>(render: Option<&RawStr>, cache: &'c RenderCache) -> Result<Option<&'c RenderCache>, ApiError> {
+    match render {
+        Some(value) if render_html(value).is_none() => Err(ApiError::BadRequest(
+            format!("Invalid value '{}' for query parameter 'render'.", value))),
+        Some(_) => Ok(Some(cache)),
+        None => Ok(None),
+    }
+}
+
+// `websearch_to_tsquery` accepts the syntax people type into search boxes: quoted phrases, `or`
+// and `-word`. Titles weigh more than bodies through the weights baked into `searchable`. The
+// body is HTML-escaped before it is cut into a snippet, so the `<mark>`s around the matches are
+// the only markup in it.
+#[cfg(not(feature = "sqlite"))]
+const SEARCH_SQL: &str = "SELECT id, title, ts_rank(searchable, query) AS rank, \
+    ts_headline('english', replace(replace(replace(body, '&', '&amp;'), '<', '&lt;'), '>', '&gt;'), query, \
+        'StartSel=<mark>, StopSel=</mark>, MaxFragments=2') AS snippet \
+    FROM posts, websearch_to_tsquery('english', $1) query \
+    WHERE searchable @@ query AND deleted_at IS NULL \
+    ORDER BY rank DESC, id DESC \
+    LIMIT $2 OFFSET $3";
+// SQLite has no full-text search built in, so there it is a plain case insensitive match on the
+// title or the body, newest first, with the start of the body as the snippet.
+#[cfg(feature = "sqlite")]
+const SEARCH_SQL: &str = "SELECT id, title, 0.0 AS rank, \
+    replace(replace(replace(substr(body, 1, 200), '&', '&amp;'), '<', '&lt;'), '>', '&gt;') AS snippet \
+    FROM posts \
+    WHERE (instr(lower(title), lower(?1)) > 0 OR instr(lower(body), lower(?1)) > 0) AND deleted_at IS NULL \
+    ORDER BY id DESC \
+    LIMIT ?2 OFFSET ?3";
+
+// Searching is left to the database, so unlike the rest of the posts API it needs one.
+#[get("/posts/search?<q>&<limit>&<offset>")]
+pub fn search_posts(q: Option<String>, limit: Option<i64>, offset: Option<i64>,
+                    connection: ReadDbConn) -> Result<Json<Vec<SearchResult>>, ApiError> {
+    let q = match q {
+        Some(ref q) if !q.trim().is_empty() => q,
+        _ => return Err(ApiError::BadRequest(String::from("Missing query parameter 'q'."))),
+    };
+
+    let results = diesel::sql_query(SEARCH_SQL)
+        .bind::<Text, _>(q)
+        .bind::<BigInt, _>(pagination::page_size(limit))
+        .bind::<BigInt, _>(offset.unwrap_or(0).max(0))
+        .load::<SearchResult>(&*connection)
+        .map_err(db_error)?;
+    Ok(Json(results))
+}
+
+// Only an ETag: the tags and comment count in the JSON change without `updated_at` moving.
+#[get("/posts/<id>?<render>")]
+pub fn get_post(id: i32, render: Option<&RawStr>, cache: State<RenderCache>,
+                repository: State<Repository>) -> Result<Conditional<Json<PostDetails>>, ApiError> {
+    let html = html_cache(render, &cache)?;
+    let details = rendered(repository.find(id)?, html);
+    Ok(Conditional { last_modified: None, version: Some(details.post.version), response: Json(details) })
+}
+
+/// A path segment such as `12.html`, naming the post to serve as a web page.
+pub struct HtmlPage(pub i32);
+
+impl UriDisplay<Path> for HtmlPage {
+    fn fmt(&self, f: &mut Formatter<Path>) -> fmt::Result {
+        f.write_value(format!("{}.html", self.0))
+    }
+}
+
+impl<'a> FromParam<'a> for HtmlPage {
+    type Error = &'a RawStr;
+
+    fn from_param(param: &'a RawStr) -> Result<HtmlPage, &'a RawStr> {
+        let page = param.as_str();
+        if !page.ends_with(".html") {
+            return Err(param);
+        }
+        page[..page.len() - ".html".len()].parse().map(HtmlPage).map_err(|_| param)
+    }
+}
+
+// Ranked below `get_post`, which turns down `12.html` as an id and forwards it here.
+#[get("/posts/<page>", rank = 2)]
+pub fn post_page(page: HtmlPage, cache: State<RenderCache>, repository: State<Repository>) -> Result<Conditional<content::Html<String>>, ApiError> {
+    let post = repository.find(page.0)?.post;
+    let title = markdown::escape(&post.title);
+    let html = content::Html(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n\
+         <body>\n<article>\n<h1>{title}</h1>\n{body}</article>\n</body>\n</html>\n",
+        title = title, body = cache.body_html(&post)));
+    Ok(Conditional { last_modified: Some(post.updated_at), version: Some(post.version), response: html })
+}
+
+/// A post looked up by slug: either found under its current slug or moved away from an old one.
+#[derive(Responder)]
+pub enum SlugLookup {
+    Found(Conditional<Json<PostDetails>>),
+    Moved(Redirect),
+}
+
+// Ranked below the other `/posts/<id>/...` routes, which would otherwise collide with it. Like
+// `get_post`, it only has an ETag.
+#[get("/posts/by-slug/<slug>?<render>", rank = 1)]
+pub fn get_post_by_slug(slug: String, render: Option<&RawStr>, cache: State<RenderCache>,
+                        repository: State<Repository>) -> Result<SlugLookup, ApiError> {
+    let html = html_cache(render, &cache)?;
+    match repository.find_by_slug(&slug)? {
+        SlugMatch::Current(details) => {
+            let details = rendered(details, html);
+            let version = Some(details.post.version);
+            Ok(SlugLookup::Found(Conditional { last_modified: None, version, response: Json(details) }))
+        }
+        SlugMatch::Former(current) => Ok(SlugLookup::Moved(Redirect::moved(uri!(get_post_by_slug: current, _)))),
+    }
+}
+
+/// Normalizes the tags sent with a post, answering a 400 for a name that can't be a tag.
+fn requested_tags(tags: Option<&Vec<String>>) -> Result<Option<Vec<String>>, ApiError> {
+    tags.map(|tags| tagging::normalize_all(tags)
+        .map_err(|name| ApiError::BadRequest(format!("Invalid tag '{}'.", name))))
+        .transpose()
+}
+
+// Answers 201 Created with a Location header pointing at the new post. Whoever is signed in
+// becomes its author.
+#[post("/posts", format = "json", data = "<new_post>")]
+pub fn create_post(new_post: Json<PostInput<NewPost>>, editor: Editor,
+                   repository: State<Repository>) -> Result<status::Created<Json<PostDetails>>, ApiError> {
+    let PostInput { post: new_post, tags, .. } = new_post.into_inner();
+    let tags = requested_tags(tags.as_ref())?.unwrap_or_default();
+    let details = repository.create(new_post, &tags, editor.username())?;
+
+    let location = uri!(get_post: details.post.id, _).to_string();
+    Ok(status::Created(location, Some(Json(details))))
+}
+
+/// The version of a post an edit is based on, and what to answer with if that isn't the
+/// current version any more: 412 when it came from `If-Match`, 409 when from the body.
+struct Precondition {
+    /// `None` for `If-Match: *`, which only asks for the post to exist.
+    version: Option<i32>,
+    mismatch: Status,
+}
+
+impl Precondition {
+    /// Answers an edit the repository turned down, with the current post if it was outdated.
+    fn rejected(&self, error: RepositoryError) -> ApiError {
+        match error {
+            RepositoryError::Outdated(current) => ApiError::Outdated(self.mismatch, current),
+            error => error.into(),
+        }
+    }
+}
+
+fn precondition(if_match: &IfMatch, version: Option<i32>) -> Result<Precondition, ApiError> {
+    match (if_match, version) {
+        (IfMatch::Any, _) => Ok(Precondition { version: None, mismatch: Status::PreconditionFailed }),
+        (IfMatch::Version(version), _) => Ok(Precondition { version: Some(*version), mismatch: Status::PreconditionFailed }),
+        (IfMatch::Missing, Some(version)) => Ok(Precondition { version: Some(version), mismatch: Status::Conflict }),
+        (IfMatch::Missing, None) => Err(ApiError::PreconditionRequired(String::from(
+            "Send the post's ETag in If-Match, or the version the edit is based on as 'version'."))),
+    }
+}
+
+// Leaving out `tags` files the post under none, the same way a missing `publish_at` clears it.
+#[put("/posts/<id>", format = "json", data = "<post>")]
+pub fn replace_post(id: i32, post: Json<PostInput<PostReplacement>>, if_match: IfMatch, editor: Editor,
+                    repository: State<Repository>) -> Result<Json<PostDetails>, ApiError> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized.into());
+    }
+    let PostInput { post, tags, version } = post.into_inner();
+    let precondition = precondition(&if_match, version)?;
+    let tags = requested_tags(tags.as_ref())?.unwrap_or_default();
+
+    repository.update(id, &PostChanges::from(post), Some(&tags), precondition.version, editor.username())
+        .map(Json)
+        .map_err(|error| precondition.rejected(error))
+}
+
+#[patch("/posts/<id>", format = "json", data = "<changes>")]
+pub fn update_post(id: i32, changes: Json<PostInput<PostChanges>>, if_match: IfMatch, editor: Editor,
+                   repository: State<Repository>) -> Result<Json<PostDetails>, ApiError> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized.into());
+    }
+    let PostInput { post: changes, tags, version } = changes.into_inner();
+    let tags = requested_tags(tags.as_ref())?;
+    if changes.is_empty() && tags.is_none() {
+        // Nothing to save, so just hand back the post as it is.
+        return Ok(Json(repository.find(id)?));
+    }
+    let precondition = precondition(&if_match, version)?;
+
+    repository.update(id, &changes, tags.as_ref().map(Vec::as_slice), precondition.version, editor.username())
+        .map(Json)
+        .map_err(|error| precondition.rejected(error))
+}
+
+// Deleting only moves the post to the trash; see `routes::trash` for restoring and purging.
+#[delete("/posts/<id>")]
+pub fn delete_post(id: i32, if_match: IfMatch, editor: Editor,
+                   repository: State<Repository>) -> Result<status::NoContent, ApiError> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized.into());
+    }
+    let precondition = precondition(&if_match, None)?;
+    repository.trash(id, precondition.version)
+        .map(|()| status::NoContent)
+        .map_err(|error| precondition.rejected(error))
+}
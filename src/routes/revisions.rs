@@ -0,0 +1,84 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+use serde::Serialize;
+
+use sk_rust_web::connection_pool::{DbConn, DbConnection};
+use sk_rust_web::diff::{self, DiffLine};
+use sk_rust_web::models::{live_posts, Post, PostRevision};
+use sk_rust_web::repository::database::{updated_post, with_revision};
+use sk_rust_web::schema::{post_revisions, posts};
+
+use super::{db_error, ApiError, Editor};
+
+fn find_revision(id: i32, rev: i32, connection: &DbConnection) -> Result<PostRevision, Status> {
+    post_revisions::table
+        .filter(post_revisions::post_id.eq(id))
+        .filter(post_revisions::id.eq(rev))
+        .first::<PostRevision>(connection)
+        .map_err(db_error)
+}
+
+#[get("/posts/<id>/revisions")]
+pub fn list_revisions(id: i32, connection: DbConn) -> Result<Json<Vec<PostRevision>>, Status> {
+    // Tell an unknown post apart from one that has never been edited.
+    live_posts().filter(posts::id.eq(id)).select(posts::id).first::<i32>(&*connection).map_err(db_error)?;
+
+    post_revisions::table
+        .filter(post_revisions::post_id.eq(id))
+        .order(post_revisions::id.desc())
+        .load::<PostRevision>(&*connection)
+        .map(Json)
+        .map_err(db_error)
+}
+
+#[get("/posts/<id>/revisions/<rev>")]
+pub fn get_revision(id: i32, rev: i32, connection: DbConn) -> Result<Json<PostRevision>, Status> {
+    find_revision(id, rev, &*connection).map(Json)
+}
+
+#[derive(Serialize)]
+pub struct RevisionDiff {
+    pub from: i32,
+    /// `None` when comparing against the post as it is now.
+    pub to: Option<i32>,
+    pub title: Vec<DiffLine>,
+    pub body: Vec<DiffLine>,
+}
+
+// Compares revision `from` with revision `to`, or with the current post when `to` is left out.
+#[get("/posts/<id>/revisions/diff?<from>&<to>")]
+pub fn diff_revisions(id: i32, from: i32, to: Option<i32>, connection: DbConn) -> Result<Json<RevisionDiff>, Status> {
+    let old = find_revision(id, from, &*connection)?;
+    let (title, body) = match to {
+        Some(to) => {
+            let new = find_revision(id, to, &*connection)?;
+            (new.title, new.body)
+        }
+        None => live_posts().filter(posts::id.eq(id))
+            .select((posts::title, posts::body))
+            .first::<(String, String)>(&*connection)
+            .map_err(db_error)?,
+    };
+
+    Ok(Json(RevisionDiff {
+        from,
+        to,
+        title: diff::diff_lines(&old.title, &title),
+        body: diff::diff_lines(&old.body, &body),
+    }))
+}
+
+// Restoring is an edit like any other, so the content it replaces becomes a revision too.
+#[post("/posts/<id>/revisions/<rev>/restore")]
+pub fn restore_revision(id: i32, rev: i32, editor: Editor, connection: DbConn) -> Result<Json<Post>, ApiError> {
+    let revision = find_revision(id, rev, &*connection)?;
+    let restored = with_revision(id, editor.username(), &*connection, || {
+        let restored = diesel::update(live_posts().filter(posts::id.eq(id)))
+            .set((posts::title.eq(&revision.title), posts::body.eq(&revision.body)))
+            .execute(&*connection)?;
+        updated_post(&*connection, id, restored)
+    }).map_err(db_error)?;
+
+    restored.map(Json).ok_or_else(|| Status::NotFound.into())
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rocket::http::{ContentType, Status};
+use rocket::response::content::{Content, Plain};
+use rocket::State;
+
+use sk_rust_web::caching::Cached;
+use sk_rust_web::connection_pool::{DbConnection, ReadDbConn};
+use sk_rust_web::models::{live_posts, PostStatus};
+use sk_rust_web::schema::posts;
+use sk_rust_web::site::Site;
+use sk_rust_web::sitemap::{self, Location, MAX_URLS};
+
+use super::db_error;
+use super::posts::HtmlPage;
+
+const SITEMAP_MAX_AGE: u32 = 60 * 60;
+const ROBOTS_MAX_AGE: u32 = 24 * 60 * 60;
+
+/// The `robots.txt` to serve, put together from the configuration at launch.
+pub struct Robots(pub String);
+
+/// Page `page` of the sitemap: the published posts, `MAX_URLS` at a time in id order.
+fn post_locations(page: i64, site: &Site, connection: &DbConnection) -> Result<Vec<Location>, Status> {
+    let posts = live_posts()
+        .filter(posts::status.eq(PostStatus::Published))
+        .select((posts::id, posts::updated_at))
+        .order(posts::id.asc())
+        .offset((page - 1) * MAX_URLS)
+        .limit(MAX_URLS)
+        .load::<(i32, DateTime<Utc>)>(connection)
+        .map_err(db_error)?;
+
+    Ok(posts.into_iter().map(|(id, updated_at)| Location {
+        url: site.url(&uri!(crate::routes::posts::post_page: HtmlPage(id)).to_string()),
+        lastmod: Some(updated_at),
+    }).collect())
+}
+
+// Once there are more published posts than fit into one sitemap, `/sitemap.xml` becomes an
+// index of `/sitemap.xml?page=1`, `?page=2` and so on.
+#[get("/sitemap.xml?<page>")]
+pub fn sitemap_xml(page: Option<i64>, site: State<Site>, connection: ReadDbConn) -> Result<Cached<Content<String>>, Status> {
+    let published = live_posts()
+        .filter(posts::status.eq(PostStatus::Published))
+        .count()
+        .get_result::<i64>(&*connection)
+        .map_err(db_error)?;
+    let pages = sitemap::page_count(published);
+
+    let xml = match page {
+        None if pages > 1 => {
+            let sitemaps = (1..=pages)
+                .map(|page| Location { url: site.url(&uri!(sitemap_xml: page).to_string()), lastmod: None })
+                .collect::<Vec<_>>();
+            sitemap::index(&sitemaps)
+        }
+        None => sitemap::urlset(&post_locations(1, &site, &*connection)?),
+        Some(page) if page >= 1 && page <= pages => sitemap::urlset(&post_locations(page, &site, &*connection)?),
+        Some(_) => return Err(Status::NotFound),
+    };
+    Ok(Cached { max_age: SITEMAP_MAX_AGE, response: Content(ContentType::XML, xml) })
+}
+
+#[get("/robots.txt")]
+pub fn robots(robots: State<Robots>) -> Cached<Plain<String>> {
+    Cached { max_age: ROBOTS_MAX_AGE, response: Plain(robots.0.clone()) }
+}
@@ -0,0 +1,58 @@
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket::http::uri::Origin;
+use rocket::request::{Form, FormParseError};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::caching::Conditional;
+use sk_rust_web::connection_pool::{DbConnection, ReadDbConn};
+use sk_rust_web::markdown::RenderCache;
+use sk_rust_web::negotiation::Negotiated;
+use sk_rust_web::models::{PostDetails, TagCount};
+use sk_rust_web::repository::Repository;
+use sk_rust_web::schema::tags;
+use sk_rust_web::tagging;
+
+use super::{db_error, ApiError};
+use super::posts::{conditional_page, list_posts, PostFilters};
+
+// Tags whose posts are all in the trash are left out, as if they weren't there.
+const TAG_COUNTS_SQL: &str = "SELECT tags.name, count(*) AS posts \
+    FROM tags \
+    JOIN post_tags ON post_tags.tag_id = tags.id \
+    JOIN posts ON posts.id = post_tags.post_id \
+    WHERE posts.deleted_at IS NULL \
+    GROUP BY tags.name \
+    ORDER BY tags.name";
+
+/// Normalizes `tag`, answering 404 for a tag nobody ever used.
+pub fn find_tag(tag: &str, connection: &DbConnection) -> Result<String, Status> {
+    let tag = tagging::normalize(tag).ok_or(Status::NotFound)?;
+    tags::table
+        .filter(tags::name.eq(&tag))
+        .select(tags::id)
+        .first::<i32>(connection)
+        .map_err(db_error)?;
+    Ok(tag)
+}
+
+#[get("/tags")]
+pub fn all_tags(connection: ReadDbConn) -> Result<Json<Vec<TagCount>>, Status> {
+    diesel::sql_query(TAG_COUNTS_SQL)
+        .load::<TagCount>(&*connection)
+        .map(Json)
+        .map_err(db_error)
+}
+
+// The same listing as `GET /posts?tag=<tag>`, paginated, filtered and sorted the same way, but
+// answering 404 for a tag nobody ever used.
+#[get("/tags/<tag>/posts?<limit>&<after>&<before>&<filters..>")]
+pub fn tag_posts(tag: String, limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                 filters: Result<Form<PostFilters>, FormParseError>, origin: &Origin,
+                 cache: State<RenderCache>, repository: State<Repository>, connection: ReadDbConn) -> Result<Conditional<Negotiated<PostDetails>>, ApiError> {
+    let tag = find_tag(&tag, &*connection)?;
+    let mut filters = filters?.into_inner();
+    filters.filter.tag = Some(tag);
+    list_posts(filters, limit, after, before, origin, &cache, &**repository).map(|page| conditional_page(page, None))
+}
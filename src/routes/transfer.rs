@@ -0,0 +1,43 @@
+use std::io::{BufReader, Read};
+
+use rocket::Data;
+use rocket::http::{ContentType, Status};
+use rocket::response::Stream;
+use rocket::response::content::Content;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::connection_pool::{DbConn, ReadDbConn};
+use sk_rust_web::transfer::{self, Export, ImportReport};
+
+use super::{db_error, Editor};
+
+/// The most an import reads; anything after it is reported rather than silently dropped.
+const IMPORT_LIMIT: u64 = 64 * 1024 * 1024;
+
+fn ndjson() -> ContentType {
+    ContentType::new("application", "x-ndjson")
+}
+
+// Streamed a batch at a time, so exporting a large site doesn't load every post into memory.
+#[get("/posts/export.ndjson")]
+pub fn export_posts(connection: ReadDbConn) -> Content<Stream<Export<ReadDbConn>>> {
+    Content(ndjson(), Stream::from(Export::new(connection)))
+}
+
+// Takes what `export_posts` hands out. Lines that can't be imported don't stop the rest; they
+// are listed in the report along with why.
+#[post("/posts/import", format = "application/x-ndjson", data = "<data>")]
+pub fn import_posts(data: Data, editor: Editor, connection: DbConn) -> Result<Json<ImportReport>, Status> {
+    let author_id = editor.user_id(&*connection).map_err(db_error)?;
+    // One byte over the limit is let through, to tell an import that is exactly the limit from
+    // one that is cut off by it.
+    let mut reader = BufReader::new(data.open().take(IMPORT_LIMIT + 1));
+    let mut report = transfer::import(&*connection, &mut reader, author_id);
+
+    if reader.get_ref().limit() == 0 {
+        let line = report.lines + 1;
+        report.reject(line, format!("The import is over {} MiB, nothing from here on was read.",
+                                    IMPORT_LIMIT / 1024 / 1024));
+    }
+    Ok(Json(report))
+}
@@ -0,0 +1,43 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use rocket::http::Status;
+use rocket_contrib::json::Json;
+
+use sk_rust_web::connection_pool::DbConn;
+use sk_rust_web::models::{Post, TrashedPost, POST_COLUMNS};
+use sk_rust_web::repository::database::updated_post;
+use sk_rust_web::schema::posts;
+
+use super::{db_error, Editor};
+
+// Most recently trashed first, which is usually the one somebody is looking for.
+#[get("/trash/posts")]
+pub fn trashed_posts(editor: Editor, connection: DbConn) -> Result<Json<Vec<TrashedPost>>, Status> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized);
+    }
+
+    posts::table
+        .filter(posts::deleted_at.is_not_null())
+        .select((POST_COLUMNS, posts::deleted_at))
+        .order(posts::deleted_at.desc())
+        .load::<TrashedPost>(&*connection)
+        .map(Json)
+        .map_err(db_error)
+}
+
+#[post("/posts/<id>/restore")]
+pub fn restore_post(id: i32, editor: Editor, connection: DbConn) -> Result<Json<Post>, Status> {
+    if editor.0.is_none() {
+        return Err(Status::Unauthorized);
+    }
+
+    let restored = diesel::update(posts::table.find(id).filter(posts::deleted_at.is_not_null()))
+        .set(posts::deleted_at.eq(None::<DateTime<Utc>>))
+        .execute(&*connection)
+        .map_err(db_error)?;
+    updated_post(&*connection, id, restored)
+        .map_err(db_error)?
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
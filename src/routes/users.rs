@@ -0,0 +1,31 @@
+use diesel::prelude::*;
+use rocket::http::uri::Origin;
+use rocket::request::{Form, FormParseError};
+use rocket::State;
+
+use sk_rust_web::caching::Conditional;
+use sk_rust_web::connection_pool::ReadDbConn;
+use sk_rust_web::markdown::RenderCache;
+use sk_rust_web::negotiation::Negotiated;
+use sk_rust_web::repository::Repository;
+use sk_rust_web::models::PostDetails;
+use sk_rust_web::schema::users;
+
+use super::{db_error, ApiError};
+use super::posts::{conditional_page, list_posts, PostFilters};
+
+// The same listing as `GET /posts?author=<id>`, but answering 404 for an unknown user.
+#[get("/users/<id>/posts?<limit>&<after>&<before>&<filters..>")]
+pub fn user_posts(id: i32, limit: Option<i64>, after: Option<i32>, before: Option<i32>,
+                  filters: Result<Form<PostFilters>, FormParseError>, origin: &Origin,
+                  cache: State<RenderCache>, repository: State<Repository>, connection: ReadDbConn) -> Result<Conditional<Negotiated<PostDetails>>, ApiError> {
+    users::table
+        .filter(users::id.eq(id))
+        .select(users::id)
+        .first::<i32>(&*connection)
+        .map_err(db_error)?;
+
+    let mut filters = filters?.into_inner();
+    filters.filter.author = Some(id);
+    list_posts(filters, limit, after, before, origin, &cache, &**repository).map(|page| conditional_page(page, None))
+}
@@ -1,8 +1,102 @@
 table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::{Comment_status, Timestamptz};
+
+    comments (id) {
+        id -> Int4,
+        post_id -> Int4,
+        parent_id -> Nullable<Int4>,
+        author -> Varchar,
+        body -> Text,
+        status -> Comment_status,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::Timestamptz;
+
+    post_revisions (id) {
+        id -> Int4,
+        post_id -> Int4,
+        title -> Varchar,
+        body -> Text,
+        editor -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::Timestamptz;
+
+    post_slugs (slug) {
+        slug -> Varchar,
+        post_id -> Int4,
+        created_at -> Timestamptz,
+    }
+}
+
+table! {
+    post_tags (post_id, tag_id) {
+        post_id -> Int4,
+        tag_id -> Int4,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::{Post_status, Timestamptz, Tsvector};
+
     posts (id) {
         id -> Int4,
         title -> Varchar,
         body -> Text,
-        published -> Bool,
+        searchable -> Tsvector,
+        status -> Post_status,
+        publish_at -> Nullable<Timestamptz>,
+        deleted_at -> Nullable<Timestamptz>,
+        slug -> Varchar,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
+        author_id -> Nullable<Int4>,
+        version -> Int4,
+    }
+}
+
+table! {
+    tags (id) {
+        id -> Int4,
+        name -> Varchar,
+    }
+}
+
+table! {
+    use diesel::sql_types::*;
+    use crate::sql_types::Timestamptz;
+
+    users (id) {
+        id -> Int4,
+        username -> Varchar,
+        display_name -> Varchar,
+        created_at -> Timestamptz,
     }
 }
+
+joinable!(comments -> posts (post_id));
+joinable!(post_revisions -> posts (post_id));
+joinable!(post_slugs -> posts (post_id));
+joinable!(post_tags -> posts (post_id));
+joinable!(post_tags -> tags (tag_id));
+joinable!(posts -> users (author_id));
+
+allow_tables_to_appear_in_same_query!(
+    comments,
+    post_revisions,
+    post_slugs,
+    post_tags,
+    posts,
+    tags,
+    users,
+);
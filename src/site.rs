@@ -0,0 +1,19 @@
+//! Where the site is served from, for the absolute URLs in feeds and sitemaps.
+
+/// Where the site is served from and what it is called. Feed readers and crawlers need absolute
+/// URLs, which a request alone can't give us behind a proxy.
+pub struct Site {
+    pub base_url: String,
+    pub title: String,
+}
+
+impl Site {
+    pub fn new(base_url: &str, title: &str) -> Site {
+        Site { base_url: base_url.trim_end_matches('/').to_string(), title: title.to_string() }
+    }
+
+    /// The absolute URL of `path`, as built by `uri!`.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
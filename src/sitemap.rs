@@ -0,0 +1,79 @@
+//! `sitemap.xml` and `robots.txt`, so that search engines can find every published post.
+
+use chrono::{DateTime, Utc};
+
+use crate::feeds::escape;
+
+/// The most URLs a single sitemap may list. Past that the sitemap becomes an index of pages.
+pub const MAX_URLS: i64 = 50_000;
+
+/// A page of the site, or a page of the sitemap in an index, and when it last changed.
+pub struct Location {
+    pub url: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+fn push_location(xml: &mut String, element: &str, location: &Location) {
+    xml.push_str(&format!("<{}>\n<loc>{}</loc>\n", element, escape(&location.url)));
+    if let Some(lastmod) = location.lastmod {
+        xml.push_str(&format!("<lastmod>{}</lastmod>\n", lastmod.to_rfc3339()));
+    }
+    xml.push_str(&format!("</{}>\n", element));
+}
+
+pub fn urlset(urls: &[Location]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for url in urls {
+        push_location(&mut xml, "url", url);
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+pub fn index(sitemaps: &[Location]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+    for sitemap in sitemaps {
+        push_location(&mut xml, "sitemap", sitemap);
+    }
+    xml.push_str("</sitemapindex>\n");
+    xml
+}
+
+/// The number of sitemap pages `urls` URLs take up.
+pub fn page_count(urls: i64) -> i64 {
+    (urls + MAX_URLS - 1) / MAX_URLS
+}
+
+/// A `robots.txt` letting every crawler in except under `disallow`, and pointing it at the
+/// sitemap.
+pub fn robots_txt(disallow: &[String], sitemap_url: &str) -> String {
+    let mut robots = String::from("User-agent: *\n");
+    for path in disallow {
+        robots.push_str(&format!("Disallow: {}\n", path));
+    }
+    if disallow.is_empty() {
+        robots.push_str("Disallow:\n");
+    }
+    robots.push_str(&format!("\nSitemap: {}\n", sitemap_url));
+    robots
+}
+
+#[cfg(test)]
+mod test {
+    use super::{page_count, robots_txt};
+
+    #[test]
+    fn sitemap_pages_hold_at_most_max_urls() {
+        assert_eq!(page_count(0), 0);
+        assert_eq!(page_count(50_000), 1);
+        assert_eq!(page_count(50_001), 2);
+    }
+
+    #[test]
+    fn robots_txt_points_at_the_sitemap() {
+        let robots = robots_txt(&[String::from("/trash/")], "http://localhost:8000/sitemap.xml");
+        assert_eq!(robots, "User-agent: *\nDisallow: /trash/\n\nSitemap: http://localhost:8000/sitemap.xml\n");
+    }
+}
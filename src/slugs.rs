@@ -0,0 +1,103 @@
+//! URL slugs for posts: generated from titles, unique across current and former slugs.
+
+use std::collections::HashSet;
+
+use deunicode::deunicode;
+use diesel::prelude::*;
+
+use crate::connection_pool::DbConnection;
+use crate::models::OldSlug;
+use crate::schema::{post_slugs, posts};
+
+const MAX_LENGTH: usize = 80;
+
+/// Turns a title into a slug: transliterated to ASCII, lowercased, with every run of other
+/// characters collapsed into a single `-`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in deunicode(title).chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_LENGTH);
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        String::from("post")
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Picks a slug for `title` that no other post uses or used to use, adding `-2`, `-3`, ...
+/// until one is free. `post_id` is the post being renamed, whose own slugs don't count.
+pub fn unique_slug(connection: &DbConnection, title: &str, post_id: Option<i32>) -> QueryResult<String> {
+    let base = slugify(title);
+    let numbered = format!("{}-%", base);
+
+    let current = posts::table
+        .filter(posts::slug.eq(&base).or(posts::slug.like(&numbered)))
+        .select((posts::slug, posts::id))
+        .load::<(String, i32)>(connection)?;
+    let former = post_slugs::table
+        .filter(post_slugs::slug.eq(&base).or(post_slugs::slug.like(&numbered)))
+        .select((post_slugs::slug, post_slugs::post_id))
+        .load::<(String, i32)>(connection)?;
+    let taken: HashSet<String> = current.into_iter()
+        .chain(former)
+        .filter(|&(_, id)| Some(id) != post_id)
+        .map(|(slug, _)| slug)
+        .collect();
+
+    Ok(first_free(&base, |slug| taken.contains(slug)))
+}
+
+/// `base` if it isn't taken, or else the first of `base-2`, `base-3`, ... that isn't.
+pub fn first_free<F: Fn(&str) -> bool>(base: &str, is_taken: F) -> String {
+    if !is_taken(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}-{}", base, n))
+        .find(|slug| !is_taken(slug))
+        .expect("ran out of numbered slugs")
+}
+
+/// Whether no post uses or used to use `slug`.
+pub fn is_free(connection: &DbConnection, slug: &str) -> QueryResult<bool> {
+    let current = posts::table.filter(posts::slug.eq(slug)).count().get_result::<i64>(connection)?;
+    let former = post_slugs::table.filter(post_slugs::slug.eq(slug)).count().get_result::<i64>(connection)?;
+    Ok(current + former == 0)
+}
+
+/// Gives post `id` a slug matching its new `title`, remembering `old_slug` so that links to it
+/// can be redirected. Returns the slug the post ends up with.
+pub fn rename(connection: &DbConnection, id: i32, old_slug: &str, title: &str) -> QueryResult<String> {
+    let slug = unique_slug(connection, title, Some(id))?;
+    if slug == old_slug {
+        return Ok(slug);
+    }
+
+    // The new slug may be one this post had before, in which case it stops being a redirect.
+    diesel::delete(post_slugs::table.find(&slug)).execute(connection)?;
+    insert_or_ignore!(post_slugs::table, &OldSlug { slug: old_slug, post_id: id }).execute(connection)?;
+    diesel::update(posts::table.find(id))
+        .set(posts::slug.eq(&slug))
+        .execute(connection)?;
+    Ok(slug)
+}
+
+#[cfg(test)]
+mod test {
+    use super::slugify;
+
+    #[test]
+    fn slugify_transliterates_and_collapses() {
+        assert_eq!(slugify("Crème Brûlée: A How-To!"), "creme-brulee-a-how-to");
+        assert_eq!(slugify("  --Hello   World--  "), "hello-world");
+        assert_eq!(slugify("¿?"), "post");
+    }
+}
@@ -0,0 +1,62 @@
+//! Database types that Diesel has no built in mapping for. On SQLite, which has no such types,
+//! each of them is stored as text.
+
+/// `TSVECTOR`, the type of the generated `posts.searchable` column. It is only ever used inside
+/// `WHERE` clauses and raw search SQL, never loaded into Rust. SQLite has no such column.
+///
+/// Named the way `diesel print-schema` spells it so regenerating `schema.rs` keeps working.
+#[derive(SqlType, QueryId)]
+#[postgres(oid = "3614", array_oid = "3643")]
+#[sqlite_type = "Text"]
+pub struct Tsvector;
+
+/// The `post_status` enum behind `posts.status`, mapped to `models::PostStatus`.
+#[allow(non_camel_case_types)]
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "post_status")]
+#[sqlite_type = "Text"]
+pub struct Post_status;
+
+/// The `comment_status` enum behind `comments.status`, mapped to `models::CommentStatus`.
+#[allow(non_camel_case_types)]
+#[derive(SqlType, QueryId)]
+#[postgres(type_name = "comment_status")]
+#[sqlite_type = "Text"]
+pub struct Comment_status;
+
+#[cfg(not(feature = "sqlite"))]
+pub use diesel::sql_types::Timestamptz;
+
+#[cfg(feature = "sqlite")]
+pub use self::sqlite::Timestamptz;
+
+#[cfg(feature = "sqlite")]
+mod sqlite {
+    use std::io::Write;
+
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use diesel::backend::Backend;
+    use diesel::deserialize::{self, FromSql};
+    use diesel::serialize::{self, Output, ToSql};
+    use diesel::sql_types::Timestamp;
+    use diesel::sqlite::Sqlite;
+
+    /// `TIMESTAMPTZ` as SQLite keeps it: text in UTC, the way Diesel writes a `Timestamp`, read
+    /// back as a `DateTime<Utc>` so that the models are the same on either database.
+    #[derive(SqlType, QueryId)]
+    #[sqlite_type = "Text"]
+    pub struct Timestamptz;
+
+    impl FromSql<Timestamptz, Sqlite> for DateTime<Utc> {
+        fn from_sql(value: Option<&<Sqlite as Backend>::RawValue>) -> deserialize::Result<Self> {
+            let at = <NaiveDateTime as FromSql<Timestamp, Sqlite>>::from_sql(value)?;
+            Ok(DateTime::from_utc(at, Utc))
+        }
+    }
+
+    impl ToSql<Timestamptz, Sqlite> for DateTime<Utc> {
+        fn to_sql<W: Write>(&self, out: &mut Output<W, Sqlite>) -> serialize::Result {
+            ToSql::<Timestamp, Sqlite>::to_sql(&self.naive_utc(), out)
+        }
+    }
+}
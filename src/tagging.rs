@@ -0,0 +1,95 @@
+//! Tags for posts: normalizing the names clients send, filing posts under them and loading
+//! them back for a page of posts.
+
+use std::collections::BTreeSet;
+
+use diesel::prelude::*;
+
+use crate::connection_pool::DbConnection;
+use crate::models::{Post, PostTag, Tag};
+use crate::schema::{post_tags, tags};
+
+pub const MAX_LENGTH: usize = 50;
+
+/// Normalizes a single tag name, trimmed and lowercased so that `Rust` and ` rust` are one tag.
+/// Names end up in `/tags/<tag>/posts`, so a `/` isn't allowed in them.
+pub fn normalize(name: &str) -> Option<String> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || name.chars().count() > MAX_LENGTH || name.contains('/') {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+/// Normalizes a list of tag names into a sorted list without duplicates, or returns the first
+/// name that isn't a valid tag.
+pub fn normalize_all(names: &[String]) -> Result<Vec<String>, &str> {
+    let mut normalized = names.iter()
+        .map(|name| normalize(name).ok_or(name.as_str()))
+        .collect::<Result<Vec<_>, _>>()?;
+    normalized.sort();
+    normalized.dedup();
+    Ok(normalized)
+}
+
+/// Files post `post_id` under exactly `names`, which must already be normalized, creating any
+/// tags that don't exist yet. Returns whether the post's tags changed.
+pub fn replace(connection: &DbConnection, post_id: i32, names: &[String]) -> QueryResult<bool> {
+    let current = post_tags::table
+        .inner_join(tags::table)
+        .filter(post_tags::post_id.eq(post_id))
+        .select(tags::name)
+        .load::<String>(connection)?;
+    // Compared as sets, as the database may well sort names differently from Rust.
+    if current.iter().collect::<BTreeSet<_>>() == names.iter().collect::<BTreeSet<_>>() {
+        return Ok(false);
+    }
+
+    diesel::delete(post_tags::table.filter(post_tags::post_id.eq(post_id))).execute(connection)?;
+    if names.is_empty() {
+        return Ok(true);
+    }
+
+    let new_tags = names.iter().map(|name| tags::name.eq(name)).collect::<Vec<_>>();
+    insert_or_ignore!(tags::table, &new_tags).execute(connection)?;
+    let filed = tags::table
+        .filter(tags::name.eq_any(names))
+        .select(tags::id)
+        .load::<i32>(connection)?
+        .into_iter()
+        .map(|tag_id| PostTag { post_id, tag_id })
+        .collect::<Vec<_>>();
+    diesel::insert_into(post_tags::table).values(&filed).execute(connection)?;
+    Ok(true)
+}
+
+/// The tag names of each of `posts`, in the same order as `posts`.
+pub fn for_posts(connection: &DbConnection, posts: &[Post]) -> QueryResult<Vec<Vec<String>>> {
+    if posts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let filed = PostTag::belonging_to(posts)
+        .inner_join(tags::table)
+        .order(tags::name)
+        .load::<(PostTag, Tag)>(connection)?;
+    Ok(filed.grouped_by(posts)
+        .into_iter()
+        .map(|filed| filed.into_iter().map(|(_, tag)| tag.name).collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::normalize_all;
+
+    #[test]
+    fn tags_are_normalized_and_deduplicated() {
+        let names = vec![String::from(" Rust"), String::from("web"), String::from("rust ")];
+        assert_eq!(normalize_all(&names), Ok(vec![String::from("rust"), String::from("web")]));
+
+        let names = vec![String::from("rust"), String::from("  ")];
+        assert_eq!(normalize_all(&names), Err("  "));
+    }
+}
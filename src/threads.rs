@@ -0,0 +1,80 @@
+//! Comment threads: nesting replies under the comments they answer, and counting the comments
+//! readers get to see.
+
+use std::collections::HashMap;
+
+use diesel::dsl::count_star;
+use diesel::prelude::*;
+
+use crate::connection_pool::DbConnection;
+use crate::models::{Comment, CommentNode, CommentStatus, Post};
+use crate::schema::comments;
+
+/// Builds the tree of `comments` from their `parent_id`s, keeping the order they come in at
+/// every level. Replies to a comment that isn't among `comments`, such as one still waiting
+/// for moderation, are left out along with everything below them.
+pub fn thread(comments: Vec<Comment>) -> Vec<CommentNode> {
+    let mut replies = HashMap::new();
+    for comment in comments {
+        replies.entry(comment.parent_id).or_insert_with(Vec::new).push(comment);
+    }
+    nest(None, &mut replies)
+}
+
+fn nest(parent_id: Option<i32>, replies: &mut HashMap<Option<i32>, Vec<Comment>>) -> Vec<CommentNode> {
+    replies.remove(&parent_id)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|comment| CommentNode { replies: nest(Some(comment.id), replies), comment })
+        .collect()
+}
+
+/// The number of approved comments on each of `posts`, in the same order as `posts`.
+pub fn approved_counts(connection: &DbConnection, posts: &[Post]) -> QueryResult<Vec<i64>> {
+    if posts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let counts = Comment::belonging_to(posts)
+        .filter(comments::status.eq(CommentStatus::Approved))
+        .group_by(comments::post_id)
+        .select((comments::post_id, count_star()))
+        .load::<(i32, i64)>(connection)?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    Ok(posts.iter().map(|post| counts.get(&post.id).cloned().unwrap_or(0)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::Utc;
+
+    use crate::models::{Comment, CommentNode, CommentStatus};
+    use super::thread;
+
+    fn comment(id: i32, parent_id: Option<i32>) -> Comment {
+        Comment {
+            id,
+            post_id: 1,
+            parent_id,
+            author: String::from("reader"),
+            body: String::from("Nice post."),
+            status: CommentStatus::Approved,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn shape(nodes: &[CommentNode]) -> Vec<(i32, Vec<i32>)> {
+        nodes.iter()
+            .map(|node| (node.comment.id, node.replies.iter().map(|reply| reply.comment.id).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn replies_nest_under_their_parents() {
+        let tree = thread(vec![comment(1, None), comment(2, Some(1)), comment(3, None),
+                               comment(4, Some(2)), comment(5, Some(1)), comment(6, Some(9))]);
+        assert_eq!(shape(&tree), vec![(1, vec![2, 5]), (3, vec![])]);
+        assert_eq!(shape(&tree[0].replies), vec![(2, vec![4]), (5, vec![])]);
+    }
+}
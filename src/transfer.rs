@@ -0,0 +1,262 @@
+//! Moving posts between environments as NDJSON, one post per line: exporting them a batch at a
+//! time and importing them back with a report of the lines that couldn't be.
+
+use std::io::{self, BufRead, Cursor, Read};
+use std::ops::Deref;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::result::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::authors;
+use crate::connection_pool::DbConnection;
+use crate::dialect;
+use crate::models::{live_posts, Post, PostStatus, POST_COLUMNS};
+use crate::schema::posts;
+use crate::slugs;
+use crate::tagging;
+
+/// How many posts an export loads from the database at a time.
+pub const EXPORT_BATCH: i64 = 500;
+/// How many posts an import saves per transaction.
+pub const IMPORT_BATCH: usize = 100;
+
+/// A post as a line of NDJSON. Exports fill in everything; an import only needs the title and
+/// body, and generates a slug and takes the importer as the author when they are left out.
+#[derive(Serialize, Deserialize)]
+pub struct PostRecord {
+    pub title: String,
+    pub body: String,
+    #[serde(default = "draft")]
+    pub status: PostStatus,
+    #[serde(default)]
+    pub publish_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// The author's username, as user ids differ from one environment to the next.
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+fn draft() -> PostStatus {
+    PostStatus::Draft
+}
+
+/// Every post outside the trash as NDJSON, in id order. Posts are loaded `EXPORT_BATCH` at a
+/// time as the reader asks for more, so the whole table never has to fit in memory.
+pub struct Export<C> {
+    connection: C,
+    after: i32,
+    lines: Cursor<Vec<u8>>,
+    done: bool,
+}
+
+impl<C: Deref<Target = DbConnection>> Export<C> {
+    pub fn new(connection: C) -> Export<C> {
+        Export { connection, after: 0, lines: Cursor::new(Vec::new()), done: false }
+    }
+
+    fn next_batch(&mut self) -> QueryResult<Vec<u8>> {
+        let connection = &*self.connection;
+        let posts = live_posts()
+            .filter(posts::id.gt(self.after))
+            .select(POST_COLUMNS)
+            .order(posts::id.asc())
+            .limit(EXPORT_BATCH)
+            .load::<Post>(connection)?;
+        self.done = posts.len() < EXPORT_BATCH as usize;
+        self.after = posts.last().map_or(self.after, |post| post.id);
+
+        let tags = tagging::for_posts(connection, &posts)?;
+        let authors = authors::for_posts(connection, &posts)?;
+        let mut lines = Vec::new();
+        for ((post, tags), author) in posts.into_iter().zip(tags).zip(authors) {
+            let record = PostRecord {
+                title: post.title,
+                body: post.body,
+                status: post.status,
+                publish_at: post.publish_at,
+                slug: Some(post.slug),
+                created_at: Some(post.created_at),
+                author: author.map(|author| author.username),
+                tags,
+            };
+            serde_json::to_writer(&mut lines, &record).expect("a post always serializes");
+            lines.push(b'\n');
+        }
+        Ok(lines)
+    }
+}
+
+impl<C: Deref<Target = DbConnection>> Read for Export<C> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.lines.read(buf)?;
+            if read > 0 || self.done || buf.is_empty() {
+                return Ok(read);
+            }
+            let lines = self.next_batch().map_err(|error| io::Error::new(io::ErrorKind::Other, error))?;
+            self.lines = Cursor::new(lines);
+        }
+    }
+}
+
+/// A line of an import that wasn't imported, and why. Lines are numbered from 1.
+#[derive(Serialize)]
+pub struct LineError {
+    pub line: usize,
+    pub error: String,
+}
+
+/// What became of an import: how many lines were read, how many posts were imported and which
+/// lines weren't, in line order.
+#[derive(Default, Serialize)]
+pub struct ImportReport {
+    pub lines: usize,
+    pub imported: usize,
+    pub errors: Vec<LineError>,
+}
+
+impl ImportReport {
+    pub fn reject(&mut self, line: usize, error: String) {
+        self.errors.push(LineError { line, error });
+    }
+}
+
+/// Why a single post couldn't be imported: something wrong with the line itself, or the
+/// database failing to save it.
+enum Rejected {
+    Invalid(String),
+    Database(Error),
+}
+
+impl From<Error> for Rejected {
+    fn from(error: Error) -> Rejected {
+        Rejected::Database(error)
+    }
+}
+
+impl Rejected {
+    fn into_message(self) -> String {
+        match self {
+            Rejected::Invalid(message) => message,
+            Rejected::Database(error) => format!("Couldn't be saved: {}", error),
+        }
+    }
+}
+
+fn import_post(connection: &DbConnection, record: &PostRecord, author_id: Option<i32>) -> Result<(), Rejected> {
+    let tags = tagging::normalize_all(&record.tags)
+        .map_err(|name| Rejected::Invalid(format!("Invalid tag '{}'.", name)))?;
+    let author_id = match record.author {
+        Some(ref username) => Some(authors::find_id(connection, username)?
+            .ok_or_else(|| Rejected::Invalid(format!("Unknown author '{}'.", username)))?),
+        None => author_id,
+    };
+    let slug = match record.slug {
+        Some(ref slug) if slugs::slugify(slug) != *slug =>
+            return Err(Rejected::Invalid(format!("Invalid slug '{}'.", slug))),
+        Some(ref slug) if !slugs::is_free(connection, slug)? =>
+            return Err(Rejected::Invalid(format!("Slug '{}' is already taken.", slug))),
+        Some(ref slug) => slug.clone(),
+        None => slugs::unique_slug(connection, &record.title, None)?,
+    };
+
+    // Unlike a new post, an imported one keeps the status, schedule, slug and creation date it
+    // had where it was exported from; a missing `created_at` is now.
+    diesel::insert_into(posts::table)
+        .values((
+            posts::title.eq(&record.title),
+            posts::body.eq(&record.body),
+            posts::status.eq(record.status),
+            posts::publish_at.eq(record.publish_at),
+            posts::slug.eq(&slug),
+            posts::author_id.eq(author_id),
+            record.created_at.map(|at| posts::created_at.eq(dialect::bind(posts::created_at, at))),
+        ))
+        .execute(connection)?;
+    let post_id = dialect::last_insert_id(connection)?;
+    tagging::replace(connection, post_id, &tags)?;
+    Ok(())
+}
+
+/// Saves a batch of posts in one transaction. Each post gets a savepoint of its own, so one
+/// that can't be saved is reported without losing the rest of the batch.
+fn save_batch(connection: &DbConnection, batch: &[(usize, PostRecord)], author_id: Option<i32>, report: &mut ImportReport) {
+    let saved = connection.transaction::<_, Error, _>(|| {
+        Ok(batch.iter()
+            .map(|&(line, ref record)| (line, connection.transaction(|| import_post(connection, record, author_id))))
+            .collect::<Vec<_>>())
+    });
+
+    match saved {
+        Ok(results) => for (line, result) in results {
+            match result {
+                Ok(()) => report.imported += 1,
+                Err(rejected) => report.reject(line, rejected.into_message()),
+            }
+        },
+        Err(error) => for &(line, _) in batch {
+            report.reject(line, format!("Couldn't be saved: {}", error));
+        },
+    }
+}
+
+/// Imports the posts in `reader`, one JSON object per line, skipping blank lines. Posts without
+/// an author are credited to `author_id`.
+pub fn import<R: BufRead>(connection: &DbConnection, reader: R, author_id: Option<i32>) -> ImportReport {
+    let mut report = ImportReport::default();
+    let mut batch = Vec::with_capacity(IMPORT_BATCH);
+
+    for (index, line) in reader.lines().enumerate() {
+        let number = index + 1;
+        report.lines = number;
+        let line = match line {
+            Ok(line) => line,
+            Err(ref error) if error.kind() == io::ErrorKind::InvalidData => {
+                report.reject(number, String::from("Not valid UTF-8."));
+                continue;
+            }
+            Err(error) => {
+                report.reject(number, format!("Couldn't be read: {}", error));
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        match serde_json::from_str::<PostRecord>(&line) {
+            Ok(record) => batch.push((number, record)),
+            Err(error) => report.reject(number, format!("Invalid post: {}", error)),
+        }
+        if batch.len() == IMPORT_BATCH {
+            save_batch(connection, &batch, author_id, &mut report);
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        save_batch(connection, &batch, author_id, &mut report);
+    }
+
+    report.errors.sort_by_key(|error| error.line);
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::PostRecord;
+    use crate::models::PostStatus;
+
+    #[test]
+    fn records_need_only_title_and_body() {
+        let record = serde_json::from_str::<PostRecord>(r#"{"title": "Hello", "body": "World"}"#).unwrap();
+        assert_eq!(record.status, PostStatus::Draft);
+        assert!(record.slug.is_none() && record.author.is_none() && record.tags.is_empty());
+    }
+}
@@ -0,0 +1,46 @@
+//! Permanent removal of posts that have sat in the trash for longer than the retention period.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use diesel::prelude::*;
+
+use crate::background;
+use crate::connection_pool::{DbConnection, Pool};
+use crate::dialect;
+use crate::markdown::RenderCache;
+use crate::schema::posts;
+
+pub const DEFAULT_RETENTION_DAYS: i64 = 30;
+pub const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Deletes every post trashed more than `retention_days` ago, along with its revisions, and
+/// returns the ids of the posts removed.
+pub fn purge_expired(connection: &DbConnection, retention_days: i64) -> QueryResult<Vec<i32>> {
+    let cutoff = Utc::now() - chrono::Duration::days(retention_days);
+    // The ids are picked out before deleting, as SQLite can't return them from the `DELETE`.
+    // Locking them keeps a post from being restored in between.
+    dialect::write_transaction(connection, || {
+        let expired = posts::table.filter(posts::deleted_at.lt(Some(cutoff))).select(posts::id);
+        #[cfg(not(feature = "sqlite"))]
+        let expired = expired.for_update();
+        let ids = expired.load::<i32>(connection)?;
+        diesel::delete(posts::table.filter(posts::id.eq_any(&ids))).execute(connection)?;
+        Ok(ids)
+    })
+}
+
+/// Starts the purger thread, which empties expired posts out of the trash every hour and drops
+/// their rendered bodies from `cache`.
+pub fn spawn_purger(pool: Pool, retention_days: i64, cache: RenderCache) {
+    background::spawn_periodic("trash-purger", pool, PURGE_INTERVAL, move |connection| {
+        match purge_expired(connection, retention_days) {
+            Ok(ref ids) if ids.is_empty() => {}
+            Ok(ids) => {
+                cache.evict(&ids);
+                println!("Purged {} posts from the trash", ids.len());
+            }
+            Err(error) => println!("Error purging the trash: {}", error),
+        }
+    });
+}